@@ -0,0 +1,91 @@
+//! Compares the spatial grid against the naive all-pairs check used before it.
+//!
+//! Run with `cargo bench --bench broadphase`.
+
+use std::time::{Duration, Instant};
+
+use bevy::math::Vec2;
+use rand::{Rng, SeedableRng};
+
+#[path = "../src/broadphase.rs"]
+mod broadphase;
+
+use broadphase::SpatialGrid;
+
+const ARENA_SIZE: f32 = 2000.;
+const CELL_SIZE: f32 = 128.;
+const RADIUS: f32 = 30.;
+const ITERATIONS: u32 = 200;
+
+fn bodies(count: usize) -> Vec<Vec2> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    (0..count)
+        .map(|_| {
+            Vec2::new(
+                rng.gen_range(-ARENA_SIZE / 2., ARENA_SIZE / 2.),
+                rng.gen_range(-ARENA_SIZE / 2., ARENA_SIZE / 2.),
+            )
+        })
+        .collect()
+}
+
+fn naive(bodies: &[Vec2]) -> usize {
+    let mut hits = 0;
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            if bodies[i].distance_squared(bodies[j]) <= (2. * RADIUS) * (2. * RADIUS) {
+                hits += 1;
+            }
+        }
+    }
+    hits
+}
+
+fn grid(grid: &mut SpatialGrid<u32>, bodies: &[Vec2]) -> usize {
+    grid.clear();
+    for (i, position) in bodies.iter().enumerate() {
+        grid.insert(i as u32, *position, RADIUS);
+    }
+
+    let mut hits = 0;
+    let mut candidates = Vec::new();
+    for (i, position) in bodies.iter().enumerate() {
+        grid.query(*position, RADIUS, &mut candidates);
+        for j in candidates.iter().map(|j| *j as usize).filter(|j| *j > i) {
+            if position.distance_squared(bodies[j]) <= (2. * RADIUS) * (2. * RADIUS) {
+                hits += 1;
+            }
+        }
+    }
+    hits
+}
+
+fn time(mut f: impl FnMut() -> usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut hits = 0;
+    for _ in 0..ITERATIONS {
+        hits = f();
+    }
+    (start.elapsed() / ITERATIONS, hits)
+}
+
+fn main() {
+    let mut spatial_grid = SpatialGrid::new(CELL_SIZE, ARENA_SIZE / 2.);
+    println!(
+        "{:>8} {:>14} {:>14} {:>8}",
+        "bodies", "naive", "grid", "speedup"
+    );
+    for count in [50, 100, 250, 500, 1000] {
+        let bodies = bodies(count);
+        let (naive_time, naive_hits) = time(|| naive(&bodies));
+        let (grid_time, grid_hits) = time(|| grid(&mut spatial_grid, &bodies));
+        assert_eq!(naive_hits, grid_hits, "grid missed collision pairs");
+        println!(
+            "{:>8} {:>14?} {:>14?} {:>7.1}x",
+            count,
+            naive_time,
+            grid_time,
+            naive_time.as_secs_f64() / grid_time.as_secs_f64()
+        );
+    }
+}
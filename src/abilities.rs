@@ -0,0 +1,183 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ggrs::Rollback;
+use ggrs::InputStatus;
+
+use crate::{
+    collision::Broadphase,
+    components::{Energy, Input, Laser, PlayerEntity, Velocity},
+    game::{ENERGY_REGEN_DELAY, INPUT_ABILITY},
+    modes::{are_enemies, Team},
+    ships::{AbilityKind, ShipClassId, ShipClasses},
+};
+
+const DASH_IMPULSE: f32 = 0.8;
+const SHIELD_BURST_RADIUS: f32 = 150.;
+const CLOAK_ALLY_ALPHA: f32 = 0.35;
+
+pub struct AbilityStats {
+    /// Frames before the ability can be used again, counted from activation.
+    pub cooldown: u32,
+    /// Frames the ability stays active.
+    pub duration: u32,
+    pub energy_cost: f32,
+}
+
+pub fn ability_stats(kind: AbilityKind) -> AbilityStats {
+    match kind {
+        AbilityKind::Dash => AbilityStats {
+            cooldown: 90,
+            duration: 10,
+            energy_cost: 20.,
+        },
+        AbilityKind::Cloak => AbilityStats {
+            cooldown: 600,
+            duration: 180,
+            energy_cost: 30.,
+        },
+        AbilityKind::ShieldBurst => AbilityStats {
+            cooldown: 300,
+            duration: 12,
+            energy_cost: 40.,
+        },
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Ability {
+    /// Frames left before the ability can be used again.
+    pub cooldown: u32,
+    /// Frames left during which the ability is in effect.
+    pub active: u32,
+}
+
+impl Ability {
+    pub fn is_active(&self) -> bool {
+        self.active > 0
+    }
+}
+
+pub fn ability_system(
+    classes: Res<ShipClasses>,
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    mut query: Query<
+        (
+            &PlayerEntity,
+            &ShipClassId,
+            &Transform,
+            &mut Ability,
+            &mut Energy,
+            &mut Velocity,
+        ),
+        With<Rollback>,
+    >,
+) {
+    for (player, class_id, transform, mut ability, mut energy, mut velocity) in query.iter_mut() {
+        let input = match inputs[player.handle].1 {
+            InputStatus::Confirmed => inputs[player.handle].0.inp,
+            InputStatus::Predicted => inputs[player.handle].0.inp,
+            InputStatus::Disconnected => 0, // disconnected players do nothing
+        };
+
+        if ability.cooldown > 0 {
+            ability.cooldown -= 1;
+        }
+        if ability.active > 0 {
+            ability.active -= 1;
+        }
+
+        let kind = classes.get(class_id.id).ability;
+        let stats = ability_stats(kind);
+        if input & INPUT_ABILITY == 0
+            || ability.cooldown > 0
+            || !energy.try_consume(stats.energy_cost, ENERGY_REGEN_DELAY)
+        {
+            continue;
+        }
+
+        ability.cooldown = stats.cooldown;
+        ability.active = stats.duration;
+        if kind == AbilityKind::Dash {
+            let dir = transform.rotation * Vec3::X;
+            velocity.x += dir.x * DASH_IMPULSE;
+            velocity.y += dir.y * DASH_IMPULSE;
+        }
+    }
+}
+
+/// Sends enemy lasers caught in an active shield burst back the way they came,
+/// now owned by the shield's pilot.
+pub fn shield_burst_system(
+    classes: Res<ShipClasses>,
+    broadphase: Res<Broadphase>,
+    ships: Query<(&Transform, &PlayerEntity, &ShipClassId, &Ability), Without<Laser>>,
+    mut lasers: Query<(&Transform, &mut Velocity, &mut Laser), Without<PlayerEntity>>,
+) {
+    let mut bursts: Vec<(usize, Team, Vec2)> = ships
+        .iter()
+        .filter(|(_, _, class_id, ability)| {
+            ability.is_active() && classes.get(class_id.id).ability == AbilityKind::ShieldBurst
+        })
+        .map(|(transform, player, _, _)| (player.handle, player.team, transform.translation.xy()))
+        .collect();
+    bursts.sort_by_key(|(handle, _, _)| *handle);
+
+    let mut candidates = Vec::new();
+    for (handle, team, center) in bursts {
+        broadphase.query(center, SHIELD_BURST_RADIUS, &mut candidates);
+        for (_, entity) in candidates.iter() {
+            let (laser_tf, mut velocity, mut laser) = match lasers.get_mut(*entity) {
+                Ok(laser) => laser,
+                Err(_) => continue,
+            };
+            let offset = laser_tf.translation.xy() - center;
+            if laser.player_handle == handle
+                || !are_enemies(laser.player_team, team)
+                || offset.length() > SHIELD_BURST_RADIUS
+            {
+                continue;
+            }
+
+            let normal = offset.normalize_or_zero();
+            let current = Vec2::new(velocity.x, velocity.y);
+            if current.dot(normal) < 0. {
+                let reflected = current - 2. * current.dot(normal) * normal;
+                velocity.x = reflected.x;
+                velocity.y = reflected.y;
+            }
+            laser.player_handle = handle;
+            laser.player_team = team;
+        }
+    }
+}
+
+/// Hides cloaked enemies and makes cloaked allies translucent.
+pub fn cloak_visibility_system(
+    classes: Res<ShipClasses>,
+    mut ships: Query<(
+        &PlayerEntity,
+        &ShipClassId,
+        &Ability,
+        &mut Visibility,
+        &mut Sprite,
+    )>,
+) {
+    let ego_team = ships
+        .iter()
+        .find(|(player, _, _, _, _)| player.ego)
+        .map(|(player, _, _, _, _)| player.team);
+
+    for (player, class_id, ability, mut visibility, mut sprite) in ships.iter_mut() {
+        let cloaked = ability.is_active() && classes.get(class_id.id).ability == AbilityKind::Cloak;
+        let enemy = !player.ego && ego_team.map_or(false, |team| are_enemies(team, player.team));
+
+        let visible = !(cloaked && enemy);
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+        let alpha = if cloaked { CLOAK_ALLY_ALPHA } else { 1. };
+        if sprite.color.a() != alpha {
+            sprite.color.set_a(alpha);
+        }
+    }
+}
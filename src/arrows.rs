@@ -0,0 +1,158 @@
+use bevy::{prelude::*, render::camera::CameraPlugin};
+
+use crate::{
+    components::{PlayerEntity, PlayerScore, RoundEntity},
+    game::ARENA_SIZE,
+    menu::connect::LocalHandles,
+    minimap::{side_color, zone_color, MinimapSettings, Vision},
+    modes::{ctf::Flag, koth::ControlZone, team_color},
+    AppState,
+};
+
+const ARROW_SIZE: (f32, f32) = (14., 24.);
+/// Gap between the arrows and the edge of the screen.
+const ARROW_MARGIN: f32 = 24.;
+/// Scale of the arrows right past the edge, shrinking down to the minimum
+/// across the arena.
+const ARROW_MAX_SCALE: f32 = 1.2;
+const ARROW_MIN_SCALE: f32 = 0.5;
+const ARROW_ALPHA: f32 = 0.85;
+
+/// Arrow on the edge of the screen towards something out of view.
+#[derive(Component)]
+pub struct EdgeArrow {
+    target: Entity,
+}
+
+#[derive(Component)]
+pub struct HasEdgeArrow;
+
+/// Gives every other ship and every objective an arrow, hidden until needed.
+#[allow(clippy::type_complexity)]
+pub fn edge_arrow_spawn_system(
+    mut commands: Commands,
+    targets: Query<
+        (Entity, Option<&PlayerEntity>),
+        (
+            Or<(With<PlayerEntity>, With<Flag>, With<ControlZone>)>,
+            Without<HasEdgeArrow>,
+        ),
+    >,
+) {
+    for (target, player) in targets.iter() {
+        commands.entity(target).insert(HasEdgeArrow);
+        if player.map_or(false, |player| player.ego) {
+            continue;
+        }
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(ARROW_SIZE.into()),
+                    ..Default::default()
+                },
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(EdgeArrow { target })
+            .insert(RoundEntity);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn edge_arrow_system(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    state: Res<State<AppState>>,
+    local_handles: Res<LocalHandles>,
+    scores: Query<&PlayerScore>,
+    cameras: Query<(&Camera, &Transform, &OrthographicProjection), Without<EdgeArrow>>,
+    ships: Query<(&PlayerEntity, &Transform), Without<EdgeArrow>>,
+    targets: Query<
+        (
+            &Transform,
+            Option<&PlayerEntity>,
+            Option<&Visibility>,
+            Option<&Flag>,
+            Option<&ControlZone>,
+        ),
+        Without<EdgeArrow>,
+    >,
+    mut arrows: Query<(
+        Entity,
+        &EdgeArrow,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    // the area seen by the game camera, the ui one has a projection too
+    let view = cameras
+        .iter()
+        .find(|(camera, _, _)| camera.name.as_deref() == Some(CameraPlugin::CAMERA_2D))
+        .map(|(_, transform, projection)| {
+            let center = transform.translation.truncate();
+            let min = Vec2::new(projection.left, projection.bottom) * projection.scale;
+            let max = Vec2::new(projection.right, projection.top) * projection.scale;
+            (center + min, center + max)
+        });
+    let (view_min, view_max) = match view {
+        Some(view) => view,
+        None => return,
+    };
+    let center = (view_min + view_max) / 2.;
+    let half = ((view_max - view_min) / 2. - Vec2::splat(ARROW_MARGIN)).max(Vec2::ONE);
+
+    let vision = Vision::new(
+        &settings,
+        &state,
+        &local_handles,
+        scores.iter(),
+        ships.iter(),
+    );
+
+    for (entity, arrow, mut transform, mut sprite, mut visibility) in arrows.iter_mut() {
+        let (target_tf, player, target_visibility, flag, zone) = match targets.get(arrow.target) {
+            Ok(target) => target,
+            Err(_) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+        let position = target_tf.translation.truncate();
+        let (color, shown) = if let Some(player) = player {
+            (
+                side_color(player.team, player.handle),
+                vision.shows_player(player, position, target_visibility),
+            )
+        } else if let Some(flag) = flag {
+            (team_color(flag.team), true)
+        } else if let Some(zone) = zone {
+            (zone_color(zone), true)
+        } else {
+            continue;
+        };
+
+        let offset = position - center;
+        let on_screen = position.cmpge(view_min).all() && position.cmple(view_max).all();
+        if !shown || on_screen || offset == Vec2::ZERO {
+            visibility.is_visible = false;
+            continue;
+        }
+
+        // where the line towards the target leaves the screen
+        let reach = (half.x / offset.x.abs()).min(half.y / offset.y.abs());
+        let spot = center + offset * reach;
+        let beyond = (offset.length() * (1. - reach)).max(0.);
+        let scale =
+            ARROW_MAX_SCALE - (ARROW_MAX_SCALE - ARROW_MIN_SCALE) * (beyond / ARENA_SIZE).min(1.);
+
+        transform.translation.x = spot.x;
+        transform.translation.y = spot.y;
+        transform.translation.z = 10.;
+        transform.rotation = Quat::from_rotation_z(offset.y.atan2(offset.x) - 90_f32.to_radians());
+        transform.scale = Vec3::new(scale, scale, 1.);
+        sprite.color = color;
+        sprite.color.set_a(ARROW_ALPHA);
+        visibility.is_visible = true;
+    }
+}
@@ -0,0 +1,333 @@
+use bevy::{ecs::system::SystemParam, math::Vec3Swizzles, prelude::*};
+use ggrs::PlayerHandle;
+
+use crate::{
+    abilities::Ability,
+    components::{Energy, FrameCount, Laser, PlayerEntity, Velocity},
+    game::{ARENA_SIZE, INPUT_ABILITY, INPUT_LEFT, INPUT_RIGHT, INPUT_SPACE, INPUT_UP},
+    modes::{are_enemies, survival::Invader, Team, TEAM_B},
+    weapons::Weapon,
+    BASE_SPEED, TIME_STEP,
+};
+
+/// Distance under which bots open fire.
+const FIRE_RANGE: f32 = 700.;
+/// Distance from the arena border at which bots turn back.
+const WALL_MARGIN: f32 = 150.;
+/// Closest approach of a laser considered a threat.
+const DODGE_DISTANCE: f32 = 60.;
+/// Steering error tolerated before turning, in radians.
+const TURN_DEADZONE: f32 = 0.05;
+/// Bots only thrust when roughly facing where they want to go, in radians.
+const THRUST_CONE: f32 = 0.6;
+/// Energy kept aside for firing, bots stop thrusting below it.
+const ENERGY_RESERVE: f32 = 30.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for BotDifficulty {
+    fn default() -> Self {
+        BotDifficulty::Normal
+    }
+}
+
+impl BotDifficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotDifficulty::Easy => "Easy",
+            BotDifficulty::Normal => "Normal",
+            BotDifficulty::Hard => "Hard",
+        }
+    }
+
+    /// Next value of the difficulty setting, cycling through "off".
+    pub fn cycle(setting: Option<Self>) -> Option<Self> {
+        match setting {
+            None => Some(BotDifficulty::Easy),
+            Some(BotDifficulty::Easy) => Some(BotDifficulty::Normal),
+            Some(BotDifficulty::Normal) => Some(BotDifficulty::Hard),
+            Some(BotDifficulty::Hard) => None,
+        }
+    }
+
+    fn skill(&self) -> BotSkill {
+        match self {
+            BotDifficulty::Easy => BotSkill {
+                aim_error: 0.35,
+                lead: 0.,
+                fire_cone: 0.25,
+                evade_frames: 0.,
+                use_ability: false,
+                preferred_range: 500.,
+            },
+            BotDifficulty::Normal => BotSkill {
+                aim_error: 0.15,
+                lead: 0.6,
+                fire_cone: 0.15,
+                evade_frames: 20.,
+                use_ability: false,
+                preferred_range: 400.,
+            },
+            BotDifficulty::Hard => BotSkill {
+                aim_error: 0.03,
+                lead: 1.,
+                fire_cone: 0.08,
+                evade_frames: 45.,
+                use_ability: true,
+                preferred_range: 350.,
+            },
+        }
+    }
+}
+
+struct BotSkill {
+    /// Largest aiming error, in radians.
+    aim_error: f32,
+    /// Part of the target's motion anticipated when aiming, from 0 (none) to 1 (perfect lead).
+    lead: f32,
+    /// Angle to the aim point under which the trigger is pulled, in radians.
+    fire_cone: f32,
+    /// Frames ahead to look for incoming lasers, 0 never dodges.
+    evade_frames: f32,
+    /// Whether the ship's ability is used to get out of trouble.
+    use_ability: bool,
+    /// Distance kept from the target.
+    preferred_range: f32,
+}
+
+/// Bot settings picked in the menus.
+#[derive(Default)]
+pub struct BotSettings {
+    /// `None` disables bots in local matches.
+    pub difficulty: Option<BotDifficulty>,
+    /// Player slots filled by bots in online matches.
+    pub online_bots: usize,
+}
+
+/// Handles driven by a bot on this peer.
+///
+/// Bots are regular local players for GGRS: their inputs are computed here
+/// and sent to the other peers like any other.
+#[derive(Default)]
+pub struct Bots {
+    pub handles: Vec<PlayerHandle>,
+    pub difficulty: BotDifficulty,
+}
+
+impl Bots {
+    pub fn controls(&self, handle: PlayerHandle) -> bool {
+        self.handles.contains(&handle)
+    }
+}
+
+/// What bots get to see of the game.
+#[derive(SystemParam)]
+pub struct BotView<'w, 's> {
+    pub bots: Res<'w, Bots>,
+    frame: Option<Res<'w, FrameCount>>,
+    ships: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Velocity,
+            &'static PlayerEntity,
+            &'static Weapon,
+            &'static Energy,
+            &'static Ability,
+        ),
+    >,
+    lasers: Query<'w, 's, (&'static Transform, &'static Velocity, &'static Laser)>,
+    invaders: Query<'w, 's, (&'static Transform, &'static Velocity), With<Invader>>,
+}
+
+/// Computes the input bits of the bot playing the given handle.
+pub fn bot_input(handle: PlayerHandle, view: &BotView) -> u8 {
+    let (own_tf, own_velocity, own_player, weapon, energy, ability) = match view
+        .ships
+        .iter()
+        .find(|(_, _, player, _, _, _)| player.handle == handle)
+    {
+        Some(ship) => ship,
+        None => return 0,
+    };
+    let skill = view.bots.difficulty.skill();
+    let position = own_tf.translation.xy();
+    let velocity = Vec2::new(own_velocity.x, own_velocity.y);
+    let facing = (own_tf.rotation * Vec3::X).xy();
+
+    // closest enemy, invaders being on the second team
+    let enemy_ships = view
+        .ships
+        .iter()
+        .filter(|(_, _, player, _, _, _)| {
+            player.handle != handle && are_enemies(own_player.team, player.team)
+        })
+        .map(|(tf, velocity, _, _, _, _)| (tf, velocity));
+    let invaders = view
+        .invaders
+        .iter()
+        .filter(|_| are_enemies(own_player.team, TEAM_B));
+    let target = enemy_ships
+        .chain(invaders)
+        .map(|(tf, velocity)| {
+            (
+                tf.translation.xy().distance_squared(position),
+                tf.translation.xy(),
+                Vec2::new(velocity.x, velocity.y),
+            )
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+
+    // head back to the middle when there is nobody to fight
+    let mut wanted = -position;
+    let mut thrust = true;
+    let mut in_sight = false;
+    let mut distance = 0.;
+    if let Some((_, target_position, target_velocity)) = target {
+        let offset = target_position - position;
+        distance = offset.length();
+
+        // lasers inherit the shooter's velocity, so lead on the relative motion
+        let relative = target_velocity - velocity;
+        let speed = weapon.stats().projectile_speed;
+        let time = intercept_time(offset, relative, speed).unwrap_or(distance / speed);
+        let aim = offset + relative * time * skill.lead;
+
+        // a slow wobble reads more like a human's aim than jitter does
+        let frame = view.frame.as_ref().map_or(0, |frame| frame.frame);
+        let error = (frame as f32 * 0.07 + handle as f32 * 1.7).sin() * skill.aim_error;
+        wanted = rotate(aim, error);
+        thrust = distance > skill.preferred_range;
+        in_sight = distance < FIRE_RANGE;
+    }
+
+    let half = ARENA_SIZE / 2. - WALL_MARGIN;
+    if position.x.abs() > half || position.y.abs() > half {
+        wanted = -position;
+        thrust = true;
+    }
+
+    let threat = incoming_laser(
+        view,
+        handle,
+        own_player.team,
+        position,
+        velocity,
+        skill.evade_frames,
+    );
+    if let Some(dodge) = threat {
+        wanted = dodge;
+        thrust = true;
+    }
+
+    let mut inp = 0;
+    let angle = facing.angle_between(wanted);
+    if angle > TURN_DEADZONE {
+        inp |= INPUT_LEFT;
+    } else if angle < -TURN_DEADZONE {
+        inp |= INPUT_RIGHT;
+    }
+    if thrust && angle.abs() < THRUST_CONE && (threat.is_some() || energy.value > ENERGY_RESERVE) {
+        inp |= INPUT_UP;
+    }
+
+    let stats = weapon.stats();
+    let aligned = threat.is_none() && in_sight && angle.abs() < skill.fire_cone;
+    if stats.charge_frames > 0 {
+        // hold to charge, let go once charged and on target
+        let charged = weapon.charge >= stats.charge_frames;
+        if target.is_some() && distance < FIRE_RANGE * 1.5 && !(charged && aligned) {
+            inp |= INPUT_SPACE;
+        }
+    } else if aligned && energy.value >= stats.energy_cost {
+        inp |= INPUT_SPACE;
+    }
+
+    if skill.use_ability && threat.is_some() && ability.cooldown == 0 {
+        inp |= INPUT_ABILITY;
+    }
+
+    inp
+}
+
+/// Time after which a projectile fired now at `speed` meets a target at `offset`
+/// moving at `relative` velocity, if it can.
+fn intercept_time(offset: Vec2, relative: Vec2, speed: f32) -> Option<f32> {
+    let a = relative.length_squared() - speed * speed;
+    let b = 2. * offset.dot(relative);
+    let c = offset.length_squared();
+    if a.abs() < f32::EPSILON {
+        return if b < 0. { Some(-c / b) } else { None };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let t1 = (-b - root) / (2. * a);
+    let t2 = (-b + root) / (2. * a);
+    [t1, t2]
+        .iter()
+        .copied()
+        .filter(|t| *t > 0.)
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+/// Finds the first enemy laser about to hit and returns the direction to dodge it.
+fn incoming_laser(
+    view: &BotView,
+    handle: PlayerHandle,
+    team: Team,
+    position: Vec2,
+    velocity: Vec2,
+    lookahead: f32,
+) -> Option<Vec2> {
+    if lookahead <= 0. {
+        return None;
+    }
+
+    let mut earliest: Option<(f32, Vec2)> = None;
+    for (laser_tf, laser_velocity, laser) in view.lasers.iter() {
+        if laser.player_handle == handle || !are_enemies(laser.player_team, team) {
+            continue;
+        }
+        let offset = laser_tf.translation.xy() - position;
+        let per_frame =
+            (Vec2::new(laser_velocity.x, laser_velocity.y) - velocity) * TIME_STEP * BASE_SPEED;
+        let speed_squared = per_frame.length_squared();
+        if speed_squared < f32::EPSILON {
+            continue;
+        }
+        let time = -offset.dot(per_frame) / speed_squared;
+        if time <= 0. || time > lookahead {
+            continue;
+        }
+        let closest = offset + per_frame * time;
+        if closest.length() > DODGE_DISTANCE {
+            continue;
+        }
+        if earliest.map_or(true, |(t, _)| time < t) {
+            // move away from where the laser passes, sideways if it comes dead on
+            let away = -closest.normalize_or_zero();
+            let dodge = if away == Vec2::ZERO {
+                per_frame.perp().normalize()
+            } else {
+                away
+            };
+            earliest = Some((time, dodge));
+        }
+    }
+
+    earliest.map(|(_, dodge)| dodge)
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
@@ -0,0 +1,126 @@
+use bevy::math::Vec2;
+
+/// Uniform grid used to find collision candidates.
+///
+/// The grid covers a square of `2 * half_extent` centered on the origin, items
+/// outside of it are clamped into the border cells. Each item is stored in the
+/// cell holding its center, and queries widen their search by the largest
+/// radius inserted so far. Cells are scanned in a fixed order, so query results
+/// are deterministic as long as items are inserted in a deterministic order.
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    half_extent: f32,
+    columns: i32,
+    max_radius: f32,
+    cells: Vec<Vec<T>>,
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    pub fn new(cell_size: f32, half_extent: f32) -> Self {
+        let columns = ((2. * half_extent) / cell_size).ceil().max(1.) as i32;
+        Self {
+            cell_size,
+            half_extent,
+            columns,
+            max_radius: 0.,
+            cells: (0..columns * columns).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    /// Empties every cell while keeping their allocations around.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.max_radius = 0.;
+    }
+
+    pub fn insert(&mut self, item: T, center: Vec2, radius: f32) {
+        let index = self.cell_index(self.cell(center.x), self.cell(center.y));
+        self.cells[index].push(item);
+        self.max_radius = self.max_radius.max(radius);
+    }
+
+    /// Collects every item whose bounding circle may overlap the given circle into `out`.
+    pub fn query(&self, center: Vec2, radius: f32, out: &mut Vec<T>) {
+        out.clear();
+        let reach = radius + self.max_radius;
+        for y in self.cell(center.y - reach)..=self.cell(center.y + reach) {
+            for x in self.cell(center.x - reach)..=self.cell(center.x + reach) {
+                out.extend_from_slice(&self.cells[self.cell_index(x, y)]);
+            }
+        }
+    }
+
+    fn cell(&self, v: f32) -> i32 {
+        (((v + self.half_extent) / self.cell_size).floor() as i32).clamp(0, self.columns - 1)
+    }
+
+    fn cell_index(&self, x: i32, y: i32) -> usize {
+        (y * self.columns + x) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> SpatialGrid<u32> {
+        SpatialGrid::new(100., 500.)
+    }
+
+    #[test]
+    fn query_finds_nearby_items_only() {
+        let mut grid = grid();
+        grid.insert(1, Vec2::new(10., 10.), 5.);
+        grid.insert(2, Vec2::new(-400., 400.), 5.);
+
+        let mut out = Vec::new();
+        grid.query(Vec2::new(20., 0.), 5., &mut out);
+        assert_eq!(out, vec![1]);
+    }
+
+    #[test]
+    fn query_reaches_across_cells_with_the_largest_radius() {
+        let mut grid = grid();
+        // centered in the next cell, but its radius reaches the query
+        grid.insert(1, Vec2::new(160., 0.), 80.);
+
+        let mut out = Vec::new();
+        grid.query(Vec2::new(50., 0.), 5., &mut out);
+        assert_eq!(out, vec![1]);
+    }
+
+    #[test]
+    fn items_outside_the_grid_land_in_the_border_cells() {
+        let mut grid = grid();
+        grid.insert(1, Vec2::new(5000., -5000.), 1.);
+
+        let mut out = Vec::new();
+        grid.query(Vec2::new(490., -490.), 1., &mut out);
+        assert_eq!(out, vec![1]);
+    }
+
+    #[test]
+    fn query_order_follows_the_cells_then_insertion() {
+        let mut grid = grid();
+        grid.insert(3, Vec2::new(150., 0.), 1.);
+        grid.insert(1, Vec2::new(50., 0.), 1.);
+        grid.insert(2, Vec2::new(60., 0.), 1.);
+
+        let mut out = Vec::new();
+        grid.query(Vec2::new(100., 0.), 60., &mut out);
+        assert_eq!(out, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn clear_empties_the_grid() {
+        let mut grid = grid();
+        grid.insert(1, Vec2::ZERO, 300.);
+        grid.clear();
+
+        let mut out = vec![7];
+        grid.query(Vec2::ZERO, 1000., &mut out);
+        assert!(out.is_empty());
+    }
+}
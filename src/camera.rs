@@ -0,0 +1,207 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::{
+    bots::Bots,
+    components::{ExplosionToSpawn, Health, PlayerEntity, Velocity},
+    controls::LocalPlayers,
+    menu::connect::LocalHandles,
+    BASE_SPEED,
+};
+
+/// Half the height of the arena in view, picked in the settings.
+const VIEW_RADII: [f32; 3] = [450., 600., 350.];
+/// How fast the camera catches up with the ship, per second.
+const FOLLOW_SPEED: f32 = 6.;
+/// Seconds of flight the camera looks ahead of the ship.
+const LOOK_AHEAD_SECS: f32 = 0.35;
+const ZOOM_IN_KEY: KeyCode = KeyCode::Equals;
+const ZOOM_OUT_KEY: KeyCode = KeyCode::Minus;
+/// Zoom factor of one key press or one line of the mouse wheel.
+const ZOOM_STEP: f32 = 1.15;
+/// Pixels of a touchpad scroll counting as one line.
+const PIXELS_PER_LINE: f32 = 50.;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 2.5;
+const ZOOM_SPEED: f32 = 10.;
+/// Offset of the camera at full trauma, in world units at zoom 1.
+const MAX_SHAKE: f32 = 16.;
+/// Trauma lost per second.
+const SHAKE_DECAY: f32 = 1.5;
+const HIT_TRAUMA: f32 = 0.35;
+const EXPLOSION_TRAUMA: f32 = 0.4;
+/// Explosions further than this from the camera don't shake it.
+const EXPLOSION_RANGE: f32 = 800.;
+/// Room kept around the ships framed together.
+const FRAME_MARGIN: f32 = 150.;
+
+pub struct CameraSettings {
+    /// Half the height of the arena in view at zoom 1, the width follows the
+    /// aspect ratio of the window.
+    pub view_radius: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            view_radius: VIEW_RADII[0],
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn cycle_view_radius(&mut self) {
+        let index = VIEW_RADII
+            .iter()
+            .position(|radius| *radius == self.view_radius)
+            .unwrap_or(0);
+        self.view_radius = VIEW_RADII[(index + 1) % VIEW_RADII.len()];
+    }
+}
+
+/// Drives the game camera outside of the rollback schedule, so that a rollback
+/// moving the ship only shifts the point the camera eases towards.
+#[derive(Component)]
+pub struct CameraRig {
+    /// Point followed, before the shake. Unset until the ship shows up.
+    focus: Option<Vec2>,
+    zoom: f32,
+    target_zoom: f32,
+    /// From 0 to 1, the shake grows with its square.
+    trauma: f32,
+    /// Hull left to the local ships on the last frame, to notice hits.
+    last_hp: f32,
+    /// Half the height needed to fit every local ship in view.
+    fit: f32,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            focus: None,
+            zoom: 1.,
+            target_zoom: 1.,
+            trauma: 0.,
+            last_hp: 0.,
+            fit: 0.,
+        }
+    }
+}
+
+pub fn camera_zoom_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut rigs: Query<&mut CameraRig>,
+) {
+    let mut steps = 0.;
+    for event in mouse_wheel.iter() {
+        steps += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        };
+    }
+    if keyboard_input.just_pressed(ZOOM_IN_KEY) {
+        steps += 1.;
+    }
+    if keyboard_input.just_pressed(ZOOM_OUT_KEY) {
+        steps -= 1.;
+    }
+    if steps == 0. {
+        return;
+    }
+
+    for mut rig in rigs.iter_mut() {
+        rig.target_zoom = (rig.target_zoom * ZOOM_STEP.powf(-steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+/// Eases the camera towards a point ahead of the local ships and shakes it when
+/// they get hit or something blows up nearby.
+///
+/// Bevy has no viewports yet, so people sharing the machine share the camera
+/// too: it frames all of their ships, zooming out as they spread, rather than
+/// splitting the screen.
+#[allow(clippy::too_many_arguments)]
+pub fn camera_follow_system(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    local_players: Res<LocalPlayers>,
+    local_handles: Res<LocalHandles>,
+    bots: Res<Bots>,
+    players: Query<(&PlayerEntity, &Transform, &Velocity, &Health), Without<CameraRig>>,
+    explosions: Query<&ExplosionToSpawn, Added<ExplosionToSpawn>>,
+    mut cameras: Query<(&mut CameraRig, &mut Transform, &mut OrthographicProjection)>,
+) {
+    let delta = time.delta_seconds();
+    let ease = |speed: f32| 1. - (-speed * delta).exp();
+
+    let seats = local_players.seats(&local_handles, &bots);
+    let mut framed = Vec::new();
+    let mut hull = 0.;
+    for (player, ship_tf, velocity, health) in players.iter() {
+        if seats.contains(&player.handle) {
+            let ahead = Vec2::new(velocity.x, velocity.y) * BASE_SPEED * LOOK_AHEAD_SECS;
+            framed.push(ship_tf.translation.truncate() + ahead);
+            hull += health.hp;
+        }
+    }
+    let bounds = framed.iter().skip(1).fold(
+        framed.first().map(|first| (*first, *first)),
+        |bounds, point| bounds.map(|(min, max)| (min.min(*point), max.max(*point))),
+    );
+
+    for (mut rig, mut transform, mut projection) in cameras.iter_mut() {
+        if let Some((min, max)) = bounds {
+            let goal = (min + max) / 2.;
+            let focus = rig.focus.unwrap_or(goal);
+            rig.focus = Some(focus.lerp(goal, ease(FOLLOW_SPEED)));
+
+            let aspect =
+                (projection.right - projection.left) / (projection.top - projection.bottom);
+            let spread = if framed.len() > 1 {
+                (max - min) / 2. + Vec2::splat(FRAME_MARGIN)
+            } else {
+                Vec2::ZERO
+            };
+            let fit = spread.y.max(spread.x / aspect.max(f32::EPSILON));
+            rig.fit += (fit - rig.fit) * ease(ZOOM_SPEED);
+
+            if hull < rig.last_hp {
+                rig.trauma += HIT_TRAUMA;
+            }
+            rig.last_hp = hull;
+        }
+        // the camera stays put while the ships are down
+        let focus = match rig.focus {
+            Some(focus) => focus,
+            None => continue,
+        };
+
+        // explosions are spawned again on rollbacks, the trauma is capped anyway
+        for explosion in explosions.iter() {
+            let distance = explosion.translation.truncate().distance(focus);
+            if distance < EXPLOSION_RANGE {
+                rig.trauma += EXPLOSION_TRAUMA * (1. - distance / EXPLOSION_RANGE);
+            }
+        }
+        rig.trauma = rig.trauma.min(1.);
+        let elapsed = time.seconds_since_startup() as f32;
+        let shake = Vec2::new((elapsed * 47.).sin(), (elapsed * 59.).cos())
+            * rig.trauma
+            * rig.trauma
+            * MAX_SHAKE
+            * rig.zoom;
+        rig.trauma = (rig.trauma - SHAKE_DECAY * delta).max(0.);
+
+        transform.translation.x = focus.x + shake.x;
+        transform.translation.y = focus.y + shake.y;
+
+        rig.zoom += (rig.target_zoom - rig.zoom) * ease(ZOOM_SPEED);
+        let scale = (settings.view_radius * rig.zoom).max(rig.fit);
+        if (projection.scale - scale).abs() > f32::EPSILON {
+            projection.scale = scale;
+        }
+    }
+}
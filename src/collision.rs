@@ -0,0 +1,355 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+
+use crate::broadphase::SpatialGrid;
+
+const DEBUG_KEY: KeyCode = KeyCode::F3;
+const DEBUG_COLOR: Color = Color::rgba(1.0, 0.1, 0.1, 0.9);
+const DEBUG_DOT_SIZE: f32 = 3.;
+const DEBUG_DOT_SPACING: f32 = 8.;
+pub const BROADPHASE_CELL_SIZE: f32 = 128.;
+
+/// Collision candidates of the current rollback frame, keyed by rollback id so
+/// candidate order is the same on every peer.
+pub type Broadphase = SpatialGrid<(u32, Entity)>;
+
+/// Collision shape of an entity, expressed in its local (unscaled, unrotated) space.
+/// The world shape follows the entity's `Transform`.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect_value(Component)]
+pub enum Hitbox {
+    Circle { radius: f32 },
+    Obb { half_extents: Vec2 },
+}
+
+impl Default for Hitbox {
+    fn default() -> Self {
+        Hitbox::Circle { radius: 0. }
+    }
+}
+
+/// A hitbox placed in the world.
+#[derive(Clone, Copy, Debug)]
+pub enum WorldShape {
+    Circle {
+        center: Vec2,
+        radius: f32,
+    },
+    Obb {
+        center: Vec2,
+        axes: [Vec2; 2],
+        half_extents: Vec2,
+    },
+}
+
+impl Hitbox {
+    pub fn world(&self, transform: &Transform) -> WorldShape {
+        let center = transform.translation.xy();
+        let scale = transform.scale.xy();
+        match *self {
+            Hitbox::Circle { radius } => WorldShape::Circle {
+                center,
+                radius: radius * scale.max_element(),
+            },
+            Hitbox::Obb { half_extents } => {
+                let x_axis = (transform.rotation * Vec3::X).xy().normalize_or_zero();
+                WorldShape::Obb {
+                    center,
+                    axes: [x_axis, x_axis.perp()],
+                    half_extents: half_extents * scale,
+                }
+            }
+        }
+    }
+
+    /// Radius of the circle enclosing the world shape.
+    pub fn bounding_radius(&self, transform: &Transform) -> f32 {
+        let scale = transform.scale.xy();
+        match *self {
+            Hitbox::Circle { radius } => radius * scale.max_element(),
+            Hitbox::Obb { half_extents } => (half_extents * scale).length(),
+        }
+    }
+
+    /// Radius of the largest circle fitting in the world shape.
+    pub fn inner_radius(&self, transform: &Transform) -> f32 {
+        let scale = transform.scale.xy();
+        match *self {
+            Hitbox::Circle { radius } => radius * scale.min_element(),
+            Hitbox::Obb { half_extents } => (half_extents * scale).min_element(),
+        }
+    }
+
+    /// Points along the outline of the local shape, used by the debug view.
+    fn outline(&self) -> Vec<Vec2> {
+        match *self {
+            Hitbox::Circle { radius } => {
+                let count = ((2. * std::f32::consts::PI * radius) / DEBUG_DOT_SPACING)
+                    .ceil()
+                    .max(8.) as usize;
+                (0..count)
+                    .map(|i| {
+                        let angle = i as f32 / count as f32 * 2. * std::f32::consts::PI;
+                        Vec2::new(angle.cos(), angle.sin()) * radius
+                    })
+                    .collect()
+            }
+            Hitbox::Obb { half_extents } => {
+                let corners = [
+                    Vec2::new(-half_extents.x, -half_extents.y),
+                    Vec2::new(half_extents.x, -half_extents.y),
+                    Vec2::new(half_extents.x, half_extents.y),
+                    Vec2::new(-half_extents.x, half_extents.y),
+                ];
+                let mut points = Vec::new();
+                for i in 0..4 {
+                    let from = corners[i];
+                    let to = corners[(i + 1) % 4];
+                    let steps = ((to - from).length() / DEBUG_DOT_SPACING).ceil().max(1.) as usize;
+                    for step in 0..steps {
+                        points.push(from.lerp(to, step as f32 / steps as f32));
+                    }
+                }
+                points
+            }
+        }
+    }
+}
+
+impl WorldShape {
+    pub fn intersects(&self, other: &WorldShape) -> bool {
+        match (*self, *other) {
+            (
+                WorldShape::Circle {
+                    center: a,
+                    radius: ra,
+                },
+                WorldShape::Circle {
+                    center: b,
+                    radius: rb,
+                },
+            ) => a.distance_squared(b) <= (ra + rb) * (ra + rb),
+            (
+                WorldShape::Circle { center, radius },
+                WorldShape::Obb {
+                    center: obb_center,
+                    axes,
+                    half_extents,
+                },
+            )
+            | (
+                WorldShape::Obb {
+                    center: obb_center,
+                    axes,
+                    half_extents,
+                },
+                WorldShape::Circle { center, radius },
+            ) => {
+                // closest point of the box to the circle center, in box space
+                let local = center - obb_center;
+                let x = local.dot(axes[0]).clamp(-half_extents.x, half_extents.x);
+                let y = local.dot(axes[1]).clamp(-half_extents.y, half_extents.y);
+                let closest = obb_center + axes[0] * x + axes[1] * y;
+                closest.distance_squared(center) <= radius * radius
+            }
+            (
+                WorldShape::Obb {
+                    center: ca,
+                    axes: axes_a,
+                    half_extents: ha,
+                },
+                WorldShape::Obb {
+                    center: cb,
+                    axes: axes_b,
+                    half_extents: hb,
+                },
+            ) => {
+                // separating axis theorem over the four face normals
+                let delta = cb - ca;
+                axes_a.iter().chain(axes_b.iter()).all(|axis| {
+                    let projected_a =
+                        ha.x * axes_a[0].dot(*axis).abs() + ha.y * axes_a[1].dot(*axis).abs();
+                    let projected_b =
+                        hb.x * axes_b[0].dot(*axis).abs() + hb.y * axes_b[1].dot(*axis).abs();
+                    delta.dot(*axis).abs() <= projected_a + projected_b
+                })
+            }
+        }
+    }
+}
+
+pub fn hitboxes_intersect(
+    a: &Hitbox,
+    a_transform: &Transform,
+    b: &Hitbox,
+    b_transform: &Transform,
+) -> bool {
+    a.world(a_transform).intersects(&b.world(b_transform))
+}
+
+#[derive(Default)]
+pub struct HitboxDebug(pub bool);
+
+#[derive(Component)]
+pub struct HitboxOutline;
+
+#[derive(Component)]
+pub struct HasHitboxOutline;
+
+pub fn toggle_hitbox_debug(keyboard_input: Res<Input<KeyCode>>, mut debug: ResMut<HitboxDebug>) {
+    if keyboard_input.just_pressed(DEBUG_KEY) {
+        debug.0 = !debug.0;
+    }
+}
+
+pub fn hitbox_debug_system(
+    mut commands: Commands,
+    debug: Res<HitboxDebug>,
+    hitboxes: Query<(Entity, &Hitbox), Without<HasHitboxOutline>>,
+    outlined: Query<Entity, With<HasHitboxOutline>>,
+    outlines: Query<Entity, With<HitboxOutline>>,
+) {
+    if !debug.0 {
+        if debug.is_changed() {
+            for entity in outlines.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            for entity in outlined.iter() {
+                commands.entity(entity).remove::<HasHitboxOutline>();
+            }
+        }
+        return;
+    }
+
+    for (entity, hitbox) in hitboxes.iter() {
+        commands
+            .entity(entity)
+            .insert(HasHitboxOutline)
+            .with_children(|parent| {
+                for point in hitbox.outline() {
+                    parent
+                        .spawn_bundle(SpriteBundle {
+                            transform: Transform::from_xyz(point.x, point.y, 0.5),
+                            sprite: Sprite {
+                                color: DEBUG_COLOR,
+                                custom_size: Some(Vec2::splat(DEBUG_DOT_SIZE)),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(HitboxOutline);
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: Hitbox = Hitbox::Obb {
+        half_extents: Vec2::new(10., 10.),
+    };
+
+    fn rotated(x: f32, y: f32, degrees: f32) -> Transform {
+        Transform {
+            translation: Vec3::new(x, y, 0.),
+            rotation: Quat::from_rotation_z(degrees.to_radians()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rotated_boxes_overlapping() {
+        assert!(hitboxes_intersect(
+            &SQUARE,
+            &rotated(0., 0., 45.),
+            &SQUARE,
+            &rotated(10., 10., 45.),
+        ));
+    }
+
+    #[test]
+    fn rotated_boxes_apart_despite_overlapping_bounds() {
+        // their axis aligned bounds overlap, only the diagonal axis separates them
+        assert!(!hitboxes_intersect(
+            &SQUARE,
+            &rotated(0., 0., 45.),
+            &SQUARE,
+            &rotated(20., 20., 45.),
+        ));
+    }
+
+    #[test]
+    fn rotated_box_against_aligned_box() {
+        // the rotated box reaches 10 * sqrt(2) along x
+        assert!(hitboxes_intersect(
+            &SQUARE,
+            &rotated(0., 0., 0.),
+            &SQUARE,
+            &rotated(23., 0., 45.),
+        ));
+        assert!(!hitboxes_intersect(
+            &SQUARE,
+            &rotated(0., 0., 0.),
+            &SQUARE,
+            &rotated(25., 0., 45.),
+        ));
+    }
+
+    #[test]
+    fn circle_touching_a_box_corner() {
+        let box_tf = rotated(0., 0., 0.);
+        // 5 away from the (10, 10) corner
+        let circle_tf = Transform::from_xyz(13., 14., 0.);
+        assert!(hitboxes_intersect(
+            &Hitbox::Circle { radius: 5. },
+            &circle_tf,
+            &SQUARE,
+            &box_tf,
+        ));
+        assert!(!hitboxes_intersect(
+            &Hitbox::Circle { radius: 4.9 },
+            &circle_tf,
+            &SQUARE,
+            &box_tf,
+        ));
+    }
+
+    #[test]
+    fn circle_near_a_corner_but_outside_the_box() {
+        // inside the bounds of the box grown by the radius, but past the rounded corner
+        assert!(!hitboxes_intersect(
+            &SQUARE,
+            &rotated(0., 0., 0.),
+            &Hitbox::Circle { radius: 9. },
+            &Transform::from_xyz(17., 17., 0.),
+        ));
+    }
+
+    #[test]
+    fn scale_stretches_the_world_shape() {
+        // twice as wide, as tall as before
+        let stretched = Transform::from_scale(Vec3::new(2., 1., 1.));
+        let dot = Hitbox::Circle { radius: 1. };
+        assert!(hitboxes_intersect(
+            &SQUARE,
+            &stretched,
+            &dot,
+            &Transform::from_xyz(20.5, 0., 0.),
+        ));
+        assert!(!hitboxes_intersect(
+            &SQUARE,
+            &stretched,
+            &dot,
+            &Transform::from_xyz(0., 11.5, 0.),
+        ));
+
+        let grown = Transform::from_scale(Vec3::splat(3.));
+        assert!(hitboxes_intersect(
+            &Hitbox::Circle { radius: 5. },
+            &grown,
+            &dot,
+            &Transform::from_xyz(15., 0., 0.),
+        ));
+    }
+}
@@ -1,35 +1,41 @@
 use bevy::{
-    prelude::{Component, Timer, Vec2, Vec3},
+    prelude::{Component, ReflectComponent, Timer, Vec2, Vec3},
     reflect::Reflect,
 };
 use bytemuck::{Pod, Zeroable};
 
+use crate::modes::Team;
+
 #[derive(Default, Component, Debug, Reflect)]
 pub struct Velocity {
     pub x: f32,
     pub y: f32,
 }
 
-#[derive(Component)]
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
 pub struct Movable {
     pub auto_despawn: bool,
     pub steerable: bool,
 }
 
-#[derive(Component)]
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
 pub struct PlayerEntity {
     pub ego: bool,
     pub handle: usize,
-    pub team: bool,
+    pub team: Team,
     pub size: Vec2,
 }
 
-#[derive(Component)]
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
 pub struct AngularVelocity {
     pub angle: f32,
 }
 
-#[derive(Debug, Component)]
+#[derive(Default, Debug, Reflect, Component)]
+#[reflect(Component)]
 pub struct ThrustEngine {
     pub on: bool,
     pub force: f32,
@@ -47,22 +53,115 @@ pub enum MenuMainBtn {
 #[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
 pub struct Input {
     pub inp: u8,
+    /// Selected ship class plus one, 0 when unknown.
+    pub ship: u8,
+    /// 1 while the local game is paused, the simulation holds still. Never set online.
+    pub paused: u8,
+    /// 1 once the player left the match through the menu, sent for a moment
+    /// before the session is dropped.
+    pub leaving: u8,
 }
 
-#[derive(Component)]
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
 pub struct RoundEntity;
 
+/// Round entity kept when a series moves on to its next round.
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct SessionEntity;
+
 #[derive(Default, Reflect, Hash, Component)]
 #[reflect(Hash)]
 pub struct FrameCount {
     pub frame: u32,
 }
 
-#[derive(Component)]
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
 pub struct Laser {
     pub player_handle: usize,
-    pub player_team: bool,
+    pub player_team: Team,
     pub size: Vec2,
+    pub damage: f32,
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Health {
+    pub hp: f32,
+    pub max: f32,
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Energy {
+    pub value: f32,
+    pub max: f32,
+    /// Frames left before the energy starts regenerating again.
+    pub regen_delay: u32,
+}
+
+impl Energy {
+    pub fn new(max: f32) -> Self {
+        Self {
+            value: max,
+            max,
+            regen_delay: 0,
+        }
+    }
+
+    /// Spends `cost` energy if available, and delays regeneration.
+    pub fn try_consume(&mut self, cost: f32, regen_delay: u32) -> bool {
+        if self.value < cost {
+            return false;
+        }
+        self.value -= cost;
+        self.regen_delay = regen_delay;
+        true
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct PlayerScore {
+    pub handle: usize,
+    pub team: Team,
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: f32,
+    pub shots_fired: u32,
+    pub shots_hit: u32,
+    /// Handle of the player who destroyed this one last, [`KILLED_BY_INVADER`]
+    /// when it wasn't another player.
+    pub killed_by: u8,
+}
+
+/// [`PlayerScore::killed_by`] of a player destroyed by invaders.
+pub const KILLED_BY_INVADER: u8 = u8::MAX;
+
+impl PlayerScore {
+    /// Records who destroyed this player. Shooter handles that don't fit, like
+    /// the one of invader lasers, count as invaders.
+    pub fn set_killed_by(&mut self, handle: usize) {
+        self.killed_by = u8::try_from(handle)
+            .ok()
+            .filter(|handle| *handle != KILLED_BY_INVADER)
+            .unwrap_or(KILLED_BY_INVADER);
+    }
+
+    /// Handle of the player who destroyed this one last, if it was a player.
+    pub fn killer(&self) -> Option<usize> {
+        (self.killed_by != KILLED_BY_INVADER).then(|| self.killed_by as usize)
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            0.
+        } else {
+            self.shots_hit as f32 / self.shots_fired as f32
+        }
+    }
 }
 
 #[derive(Component)]
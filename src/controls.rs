@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use ggrs::PlayerHandle;
+
+use crate::{
+    bots::Bots,
+    game::{INPUT_ABILITY, INPUT_LEFT, INPUT_RIGHT, INPUT_SPACE, INPUT_SWITCH, INPUT_UP},
+    hud::NoticeText,
+    menu::{connect::LocalHandles, pause::PauseMenu},
+    AppState,
+};
+
+/// Most people sharing one machine, each with their own part of the keyboard.
+pub const MAX_LOCAL_PLAYERS: usize = 4;
+/// Stick and trigger travel ignored, picked in the settings.
+const DEADZONES: [f32; 3] = [0.2, 0.3, 0.1];
+
+pub struct KeyBindings {
+    pub thrust: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub fire: KeyCode,
+    pub switch: KeyCode,
+    pub ability: KeyCode,
+}
+
+/// Keys of each seat, the first one being the single player layout.
+pub static KEY_BINDINGS: [KeyBindings; MAX_LOCAL_PLAYERS] = [
+    KeyBindings {
+        thrust: KeyCode::Up,
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        fire: KeyCode::Space,
+        switch: KeyCode::Q,
+        ability: KeyCode::LShift,
+    },
+    KeyBindings {
+        thrust: KeyCode::W,
+        left: KeyCode::A,
+        right: KeyCode::D,
+        fire: KeyCode::F,
+        switch: KeyCode::R,
+        ability: KeyCode::E,
+    },
+    KeyBindings {
+        thrust: KeyCode::I,
+        left: KeyCode::J,
+        right: KeyCode::L,
+        fire: KeyCode::K,
+        switch: KeyCode::U,
+        ability: KeyCode::O,
+    },
+    KeyBindings {
+        thrust: KeyCode::Numpad8,
+        left: KeyCode::Numpad4,
+        right: KeyCode::Numpad6,
+        fire: KeyCode::Numpad5,
+        switch: KeyCode::Numpad7,
+        ability: KeyCode::Numpad9,
+    },
+];
+
+impl KeyBindings {
+    pub fn read(&self, keyboard_input: &Input<KeyCode>) -> u8 {
+        let mut inp: u8 = 0;
+        if keyboard_input.pressed(self.thrust) {
+            inp |= INPUT_UP
+        }
+        if keyboard_input.pressed(self.left) {
+            inp |= INPUT_LEFT
+        }
+        if keyboard_input.pressed(self.right) {
+            inp |= INPUT_RIGHT
+        }
+        if keyboard_input.pressed(self.fire) {
+            inp |= INPUT_SPACE
+        }
+        if keyboard_input.just_pressed(self.switch) {
+            inp |= INPUT_SWITCH
+        }
+        if keyboard_input.just_pressed(self.ability) {
+            inp |= INPUT_ABILITY
+        }
+        inp
+    }
+}
+
+/// People playing on this machine, picked in the main menu.
+pub struct LocalPlayers {
+    pub count: usize,
+}
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        Self { count: 1 }
+    }
+}
+
+impl LocalPlayers {
+    /// Cycles from one player up to as many as the mode has room for.
+    pub fn cycle(&mut self, num_players: usize) {
+        self.count = self.count % MAX_LOCAL_PLAYERS.min(num_players) + 1;
+    }
+
+    pub fn clamp(&mut self, num_players: usize) {
+        self.count = self.count.clamp(1, MAX_LOCAL_PLAYERS.min(num_players));
+    }
+
+    /// Handles played by people on this machine, in seat order. Online a peer
+    /// only ever has one.
+    pub fn seats(&self, local_handles: &LocalHandles, bots: &Bots) -> Vec<PlayerHandle> {
+        local_handles
+            .handles
+            .iter()
+            .copied()
+            .filter(|handle| !bots.controls(*handle))
+            .take(self.count)
+            .collect()
+    }
+}
+
+pub struct ControllerSettings {
+    pub deadzone: f32,
+}
+
+impl Default for ControllerSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: DEADZONES[0],
+        }
+    }
+}
+
+impl ControllerSettings {
+    pub fn cycle_deadzone(&mut self) {
+        let index = DEADZONES
+            .iter()
+            .position(|deadzone| *deadzone == self.deadzone)
+            .unwrap_or(0);
+        self.deadzone = DEADZONES[(index + 1) % DEADZONES.len()];
+    }
+}
+
+/// Controllers plugged in, each playing the seat of its slot. An unplugged
+/// controller leaves its slot empty for the next one, so that the others keep
+/// their seats.
+#[derive(Default)]
+pub struct Controllers {
+    slots: Vec<Option<Gamepad>>,
+    /// Seat of the first slot, the seats before it stay on the keyboard.
+    pub first_seat: usize,
+}
+
+impl Controllers {
+    pub fn count(&self) -> usize {
+        self.pads().count()
+    }
+
+    pub fn pads(&self) -> impl Iterator<Item = Gamepad> + '_ {
+        self.slots.iter().flatten().copied()
+    }
+
+    pub fn cycle_first_seat(&mut self) {
+        self.first_seat = (self.first_seat + 1) % MAX_LOCAL_PLAYERS;
+    }
+
+    fn seat_of(&self, pad: Gamepad) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| *slot == Some(pad))
+            .map(|slot| slot + self.first_seat)
+    }
+
+    /// Input of the controller playing this seat, nothing without one.
+    pub fn read(
+        &self,
+        seat: usize,
+        settings: &ControllerSettings,
+        buttons: &Input<GamepadButton>,
+        button_axes: &Axis<GamepadButton>,
+        axes: &Axis<GamepadAxis>,
+    ) -> u8 {
+        let pad = match seat
+            .checked_sub(self.first_seat)
+            .and_then(|slot| self.slots.get(slot).copied().flatten())
+        {
+            Some(pad) => pad,
+            None => return 0,
+        };
+        let pressed = |button| buttons.pressed(GamepadButton(pad, button));
+        let just_pressed = |button| buttons.just_pressed(GamepadButton(pad, button));
+        let steer = axes
+            .get(GamepadAxis(pad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.);
+        let trigger = button_axes
+            .get(GamepadButton(pad, GamepadButtonType::RightTrigger2))
+            .unwrap_or(0.);
+
+        let mut inp: u8 = 0;
+        if trigger > settings.deadzone || pressed(GamepadButtonType::DPadUp) {
+            inp |= INPUT_UP
+        }
+        if steer < -settings.deadzone || pressed(GamepadButtonType::DPadLeft) {
+            inp |= INPUT_LEFT
+        }
+        if steer > settings.deadzone || pressed(GamepadButtonType::DPadRight) {
+            inp |= INPUT_RIGHT
+        }
+        if pressed(GamepadButtonType::South) || pressed(GamepadButtonType::RightTrigger) {
+            inp |= INPUT_SPACE
+        }
+        if just_pressed(GamepadButtonType::West) {
+            inp |= INPUT_SWITCH
+        }
+        if just_pressed(GamepadButtonType::East) || just_pressed(GamepadButtonType::LeftTrigger) {
+            inp |= INPUT_ABILITY
+        }
+        inp
+    }
+}
+
+/// Seats controllers as they get plugged in, in the menus as well as in rounds.
+/// Unplugging one mid-round opens the pause menu, holding a local game still
+/// until it is back.
+pub fn controller_connection_system(
+    mut events: EventReader<GamepadEvent>,
+    mut controllers: ResMut<Controllers>,
+    mut pause_menu: ResMut<PauseMenu>,
+    state: Res<State<AppState>>,
+    mut notices: Query<(&mut Text, &mut NoticeText)>,
+) {
+    for GamepadEvent(pad, event_type) in events.iter() {
+        let message = match event_type {
+            GamepadEventType::Connected => {
+                if controllers.seat_of(*pad).is_none() {
+                    match controllers.slots.iter().position(Option::is_none) {
+                        Some(slot) => controllers.slots[slot] = Some(*pad),
+                        None => controllers.slots.push(Some(*pad)),
+                    }
+                }
+                controllers
+                    .seat_of(*pad)
+                    .map(|seat| format!("Controller connected for player {}", seat + 1))
+            }
+            GamepadEventType::Disconnected => {
+                let seat = controllers.seat_of(*pad);
+                for slot in controllers.slots.iter_mut() {
+                    if *slot == Some(*pad) {
+                        *slot = None;
+                    }
+                }
+                let in_round = matches!(
+                    state.current(),
+                    AppState::RoundLocal | AppState::RoundOnline
+                );
+                if seat.is_some() && in_round {
+                    pause_menu.open = true;
+                }
+                seat.map(|seat| format!("Controller of player {} disconnected", seat + 1))
+            }
+            _ => None,
+        };
+        if let Some(message) = message {
+            for (mut text, mut notice) in notices.iter_mut() {
+                notice.show(&mut text, &message);
+            }
+        }
+    }
+}
@@ -1,82 +1,171 @@
 use crate::{
+    abilities::Ability,
+    bots::{bot_input, BotView, Bots},
+    camera::{CameraRig, CameraSettings},
     checksum::Checksum,
+    collision::Hitbox,
     components::{
-        AngularVelocity, FrameCount, Input, Movable, PlayerEntity, RoundEntity, ThrustEngine,
-        Velocity,
+        AngularVelocity, Energy, FrameCount, Health, Input, Laser, Movable, PlayerEntity,
+        PlayerScore, RoundEntity, SessionEntity, ThrustEngine, Velocity,
     },
-    menu::{connect::LocalHandles, win::MatchData},
-    AppState, GGRSConfig, ImageAssets, NUM_ALLIES, NUM_ENNEMIES, NUM_PLAYERS, PLAYER_SCALE,
+    controls::{ControllerSettings, Controllers, LocalPlayers, KEY_BINDINGS},
+    hud::NoticeText,
+    menu::{
+        connect::LocalHandles,
+        pause::PauseMenu,
+        win::{MatchData, ScoreLine},
+    },
+    modes::{
+        ctf::{flag_bundle, Flag, FlagScore},
+        koth::{spawn_zone_outline, zone_bundle, ControlZone, HillScore},
+        opposing_team, player_color,
+        survival::{invader_bundle, Invader, Waves},
+        team_name, GameMode, Team, NO_TEAM, TEAM_A,
+    },
+    pickups::{pickup_bundle, Pickup, PowerUps},
+    round::{LeftPlayers, NextRoundVote, RoundTimer, Series},
+    ships::{SelectedShip, ShipClassId, ShipClasses, Steering, DEFAULT_SHIP_CLASS},
+    weapons::{laser_bundle, Weapon},
+    AppState, GGRSConfig, ImageAssets, MAX_PREDICTION, PLAYER_SCALE,
 };
-use bevy::render::camera::{CameraPlugin, CameraProjection, DepthCalculation};
-use bevy::render::primitives::Frustum;
-use bevy::render::view::VisibleEntities;
+use bevy::render::camera::ScalingMode;
 use bevy::{math::Vec3, prelude::OrthographicCameraBundle};
 use bevy::{math::Vec3Swizzles, prelude::*};
 use bevy_ggrs::{Rollback, RollbackIdProvider, SessionType};
-use ggrs::{InputStatus, P2PSession, PlayerHandle};
+use ggrs::{GGRSEvent, InputStatus, P2PSession, PlayerHandle};
 
 pub(crate) const INPUT_UP: u8 = 0b0001;
 pub(crate) const INPUT_LEFT: u8 = 0b0100;
 pub(crate) const INPUT_RIGHT: u8 = 0b1000;
 pub(crate) const INPUT_SPACE: u8 = 0b0010;
+pub(crate) const INPUT_SWITCH: u8 = 0b10000;
+pub(crate) const INPUT_ABILITY: u8 = 0b100000;
+/// Vote for the next round, only sent between rounds.
+pub(crate) const INPUT_READY: u8 = 0b1000000;
+/// Vote for swapping the teams on a rematch, only sent between rounds.
+pub(crate) const INPUT_SWAP: u8 = 0b10000000;
 pub(crate) const LASER_SPEED: f32 = 50.;
+pub(crate) const MAX_ENERGY: f32 = 100.;
+pub(crate) const ENERGY_REGEN: f32 = 0.4;
+pub(crate) const ENERGY_REGEN_DELAY: u32 = 30;
+pub(crate) const THRUST_ENERGY_COST: f32 = 0.15;
+pub(crate) const SHIP_RESTITUTION: f32 = 1.;
+pub(crate) const RAM_DAMAGE_FACTOR: f32 = 40.;
+pub(crate) const RAM_MIN_SPEED: f32 = 0.5;
 pub(crate) const ARENA_SIZE: f32 = 2000.0;
 const PLAYER_SIZE: f32 = 50.;
 const TILE_SIZE: f32 = 200.;
 const TILE_COLORS: [Color; 2] = [Color::DARK_GRAY, Color::ANTIQUE_WHITE];
 
-pub fn input(handle: In<PlayerHandle>, keyboard_input: Res<bevy::input::Input<KeyCode>>) -> Input {
-    let mut inp: u8 = 0;
-    if keyboard_input.pressed(KeyCode::Up) {
-        inp |= INPUT_UP
-    }
-    if keyboard_input.pressed(KeyCode::Left) {
-        inp |= INPUT_LEFT
+#[allow(clippy::too_many_arguments)]
+pub fn input(
+    handle: In<PlayerHandle>,
+    keyboard_input: Res<bevy::input::Input<KeyCode>>,
+    gamepad_buttons: Res<bevy::input::Input<GamepadButton>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    controllers: Res<Controllers>,
+    controller_settings: Res<ControllerSettings>,
+    selected_ship: Res<SelectedShip>,
+    classes: Res<ShipClasses>,
+    state: Res<State<AppState>>,
+    vote: Res<NextRoundVote>,
+    pause_menu: Res<PauseMenu>,
+    local_players: Res<LocalPlayers>,
+    local_handles: Res<LocalHandles>,
+    bot_view: BotView,
+) -> Input {
+    let bot = bot_view.bots.controls(handle.0);
+    let ship = if bot {
+        (handle.0 % classes.0.len()) as u8 + 1
+    } else {
+        selected_ship.0 + 1
+    };
+
+    // between rounds, only the vote for the next one goes through, bots
+    // agreeing to anything
+    let leaving = pause_menu.leaving.is_some() as u8;
+    if *state.current() == AppState::Win {
+        let mut inp = 0;
+        if vote.ready || bot {
+            inp |= INPUT_READY;
+        }
+        if vote.swap_sides || bot {
+            inp |= INPUT_SWAP;
+        }
+        return Input {
+            inp,
+            ship,
+            paused: 0,
+            leaving,
+        };
     }
-    if keyboard_input.pressed(KeyCode::Right) {
-        inp |= INPUT_RIGHT
+
+    // the pause menu holds a local game still, an online one goes on without us
+    let paused = (pause_menu.open && *state.current() == AppState::RoundLocal) as u8;
+    if bot {
+        return Input {
+            inp: bot_input(handle.0, &bot_view),
+            ship,
+            paused,
+            leaving,
+        };
     }
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        inp |= INPUT_SPACE
+    if pause_menu.open || leaving != 0 {
+        return Input {
+            inp: 0,
+            ship,
+            paused,
+            leaving,
+        };
     }
 
-    Input { inp }
+    // each person at the keyboard has their own keys, and maybe a controller,
+    // handles left without anyone to play them stay idle
+    let seat = local_players
+        .seats(&local_handles, &bot_view.bots)
+        .iter()
+        .position(|seat| *seat == handle.0);
+    let inp = match seat {
+        Some(seat) => {
+            KEY_BINDINGS[seat].read(&keyboard_input)
+                | controllers.read(
+                    seat,
+                    &controller_settings,
+                    &gamepad_buttons,
+                    &gamepad_button_axes,
+                    &gamepad_axes,
+                )
+        }
+        None => 0,
+    };
+
+    Input {
+        inp,
+        ship,
+        paused: 0,
+        leaving,
+    }
 }
 
-pub fn setup_camera(mut commands: Commands, local_handles: Res<LocalHandles>) {
-    let far = 500.0;
-    let orthographic_projection = OrthographicProjection {
-        left: 0.0,
-        right: 500.0,
-        top: 500.0,
-        bottom: 100.0,
-        depth_calculation: DepthCalculation::ZDifference,
-        ..Default::default()
-    };
-    let transform = Transform::from_xyz(0.0, 0.0, far - 0.1);
-    let view_projection =
-        orthographic_projection.get_projection_matrix() * transform.compute_matrix().inverse();
-    let frustum = Frustum::from_view_projection(
-        &view_projection,
-        &transform.translation,
-        &transform.back(),
-        orthographic_projection.far(),
-    );
-    let camera_bundle = OrthographicCameraBundle {
-        camera: Camera {
-            name: Some(CameraPlugin::CAMERA_2D.to_string()),
-            near: orthographic_projection.near,
-            far: orthographic_projection.far,
-            ..Default::default()
-        },
-        orthographic_projection,
-        visible_entities: VisibleEntities::default(),
-        frustum,
-        transform,
-        global_transform: Default::default(),
-    };
+pub fn setup_camera(mut commands: Commands, settings: Res<CameraSettings>) {
+    // the view keeps the same height of the arena whatever the window, its width
+    // follows the aspect ratio, on resizes and on the web canvas too
+    let mut camera_bundle = OrthographicCameraBundle::new_2d();
+    camera_bundle.orthographic_projection.scaling_mode = ScalingMode::FixedVertical;
+    camera_bundle.orthographic_projection.scale = settings.view_radius;
+
+    commands
+        .spawn_bundle(camera_bundle)
+        .insert(CameraRig::default())
+        .insert(RoundEntity)
+        .insert(SessionEntity);
 
-    commands.spawn_bundle(camera_bundle).insert(RoundEntity);
+    // ui camera for in-round overlays
+    commands
+        .spawn_bundle(UiCameraBundle::default())
+        .insert(RoundEntity)
+        .insert(SessionEntity);
 }
 
 pub fn setup_round(mut commands: Commands, game_textures: Res<ImageAssets>) {
@@ -102,7 +191,8 @@ pub fn setup_round(mut commands: Commands, game_textures: Res<ImageAssets>) {
                     },
                     ..Default::default()
                 })
-                .insert(RoundEntity);
+                .insert(RoundEntity)
+                .insert(SessionEntity);
         }
     }
 }
@@ -112,89 +202,310 @@ pub fn spawn_players(
     mut rip: ResMut<RollbackIdProvider>,
     game_textures: Res<ImageAssets>,
     local_handles: Res<LocalHandles>,
+    classes: Res<ShipClasses>,
+    mode: Res<GameMode>,
+    series: Query<&Series>,
 ) {
-    let r = ARENA_SIZE / 4.;
+    let ego_handle = *local_handles.handles.first().unwrap();
+    let num_players = mode.num_players();
+    let swapped = series.iter().any(|series| series.swapped);
 
-    let ego_handle = local_handles.handles.first().unwrap();
-    let mut spawn_player = |transform: &Transform, team: bool, handle: &usize| {
+    for handle in 0..num_players {
+        let team = if swapped {
+            opposing_team(mode.team(handle))
+        } else {
+            mode.team(handle)
+        };
+        // players who left sit the next rounds out
+        if !series.iter().any(|series| series.has_left(handle)) {
+            spawn_ship(
+                &mut commands,
+                &mut rip,
+                &game_textures,
+                &classes,
+                handle,
+                team,
+                handle == ego_handle,
+                spawn_location(handle, num_players),
+            );
+        }
+
+        // score line, kept on its own entity so it outlives the ship
         commands
-            .spawn_bundle(SpriteBundle {
-                transform: *transform,
-                texture: if team {
-                    game_textures.ennemy.clone()
-                } else {
-                    game_textures.ally.clone()
-                },
+            .spawn()
+            .insert(PlayerScore {
+                handle,
+                team,
                 ..Default::default()
             })
-            .insert(Velocity::default())
-            .insert(Movable {
-                auto_despawn: false,
-                steerable: true,
-            })
-            .insert(AngularVelocity { angle: 0. })
-            .insert(ThrustEngine {
-                on: false,
-                force: 0.001,
-            })
-            .insert(Checksum::default())
             .insert(Rollback::new(rip.next_id()))
-            .insert(RoundEntity)
-            .insert(PlayerEntity {
-                ego: if handle == ego_handle { true } else { false },
-                handle: *handle,
-                team,
-                size: match team {
-                    true => Vec2::new(75.0, 98.0),
-                    false => Vec2::new(84.0, 93.0),
-                },
-            });
-    };
+            .insert(RoundEntity);
+    }
+}
 
-    let get_spawn_location = |handle: usize| -> Transform {
-        let rot = handle as f32 / NUM_PLAYERS as f32 * 2. * std::f32::consts::PI;
-        let x = r * rot.cos();
-        let y = r * rot.sin();
+/// Spawn point number `index` out of `count`, evenly spread on a circle.
+pub fn spawn_location(index: usize, count: usize) -> Vec2 {
+    let r = ARENA_SIZE / 4.;
+    let rot = index as f32 / count as f32 * 2. * std::f32::consts::PI;
+    Vec2::new(r * rot.cos(), r * rot.sin())
+}
+
+/// Spawns a player's ship. Every ship starts with the default class, the
+/// chosen one arrives with the inputs.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_ship(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    game_textures: &ImageAssets,
+    classes: &ShipClasses,
+    handle: usize,
+    team: Team,
+    ego: bool,
+    position: Vec2,
+) {
+    let class = classes.get(DEFAULT_SHIP_CLASS);
+    let size = Vec2::new(class.hitbox.0, class.hitbox.1);
 
-        let mut transform = Transform::from_translation(Vec3::new(x, y, 3.));
-        transform
+    let player = PlayerEntity {
+        ego,
+        handle,
+        team,
+        size,
+    };
+    let transform = Transform {
+        translation: Vec3::new(position.x, position.y, 3.),
+        scale: Vec3::new(class.sprite_scale, class.sprite_scale, 1.),
+        ..Default::default()
     };
 
-    let mut handle: usize = 0;
+    commands
+        .spawn_bundle(ship_bundle(game_textures, &player, transform))
+        .insert(Velocity::default())
+        .insert(Movable {
+            auto_despawn: false,
+            steerable: true,
+        })
+        .insert(AngularVelocity { angle: 0. })
+        .insert(ThrustEngine {
+            on: false,
+            force: class.thrust,
+        })
+        .insert(Steering {
+            turn_rate: class.turn_rate,
+        })
+        .insert(ShipClassId {
+            id: DEFAULT_SHIP_CLASS,
+            locked: false,
+        })
+        .insert(Health {
+            hp: class.hull,
+            max: class.hull,
+        })
+        .insert(Weapon::default())
+        .insert(Energy::new(MAX_ENERGY))
+        .insert(Ability::default())
+        .insert(PowerUps::default())
+        .insert(Checksum::default())
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity)
+        .insert(player)
+        .insert(Hitbox::Obb {
+            half_extents: size / 2.,
+        });
+}
 
-    for _ in 0..NUM_ALLIES {
-        spawn_player(&get_spawn_location(handle), false, &handle);
-        handle += 1;
+pub fn ship_bundle(
+    game_textures: &ImageAssets,
+    player: &PlayerEntity,
+    transform: Transform,
+) -> SpriteBundle {
+    SpriteBundle {
+        transform,
+        texture: if player.team == TEAM_A {
+            game_textures.ally.clone()
+        } else {
+            game_textures.ennemy.clone()
+        },
+        sprite: Sprite {
+            // without teams, a tint is all that tells players apart
+            color: if player.team == NO_TEAM {
+                player_color(player.handle)
+            } else {
+                Color::WHITE
+            },
+            ..Default::default()
+        },
+        ..Default::default()
     }
-    for _ in 0..NUM_ENNEMIES {
-        spawn_player(&get_spawn_location(handle), true, &handle);
-        handle += 1;
+}
+
+/// A rollback to before a despawn brings the entity back with its registered
+/// components only. Gives it back what it needs to be drawn.
+#[allow(clippy::type_complexity)]
+pub fn restore_rollback_entities(
+    mut commands: Commands,
+    game_textures: Res<ImageAssets>,
+    restored: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&PlayerEntity>,
+            Option<&Laser>,
+            Option<&Pickup>,
+            Option<&Invader>,
+            Option<&Flag>,
+            Option<&ControlZone>,
+        ),
+        (With<Rollback>, Without<GlobalTransform>),
+    >,
+) {
+    for (entity, transform, player, laser, pickup, invader, flag, zone) in restored.iter() {
+        let bundle = if let Some(player) = player {
+            ship_bundle(&game_textures, player, *transform)
+        } else if let Some(laser) = laser {
+            laser_bundle(&game_textures, laser, *transform)
+        } else if let Some(pickup) = pickup {
+            pickup_bundle(pickup, *transform)
+        } else if let Some(invader) = invader {
+            invader_bundle(&game_textures, invader, *transform)
+        } else if let Some(flag) = flag {
+            flag_bundle(flag, *transform)
+        } else if let Some(zone) = zone {
+            zone_bundle(zone, *transform)
+        } else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert_bundle(bundle);
+        if let Some(zone) = zone {
+            // the outline went away with the zone
+            entity_commands.with_children(|parent| spawn_zone_outline(parent, zone));
+        }
     }
 }
 
-pub fn print_p2p_events(mut session: ResMut<P2PSession<GGRSConfig>>) {
+pub fn print_p2p_events(
+    mut session: ResMut<P2PSession<GGRSConfig>>,
+    mut notices: Query<(&mut Text, &mut NoticeText)>,
+) {
     for event in session.events() {
         info!("GGRS Event: {:?}", event);
+        // a peer left through the menu or lost its connection, the game goes on without it
+        if let GGRSEvent::Disconnected { .. } = event {
+            for (mut text, mut notice) in notices.iter_mut() {
+                notice.show(&mut text, "A player left the match");
+            }
+        }
     }
 }
 
-pub fn check_win(mut state: ResMut<State<AppState>>, mut commands: Commands) {
-    let condition = false;
-    let confirmed = false;
+/// Disconnects the peers who left through the menu as soon as their leaving
+/// flag is confirmed, rather than waiting for their connection to time out.
+pub fn disconnect_left_players(
+    session: Option<ResMut<P2PSession<GGRSConfig>>>,
+    local_handles: Option<Res<LocalHandles>>,
+    mut left_players: ResMut<LeftPlayers>,
+) {
+    if left_players.handles.is_empty() {
+        return;
+    }
+    let handles = std::mem::take(&mut left_players.handles);
+    let (mut session, local_handles) = match (session, local_handles) {
+        (Some(session), Some(local_handles)) => (session, local_handles),
+        _ => return,
+    };
+    for handle in handles {
+        if !local_handles.handles.contains(&handle) {
+            // all handles of a peer go at once, the others are already gone
+            let _ = session.disconnect_player(handle);
+        }
+    }
+}
 
-    if condition && confirmed {
-        state.set(AppState::Win).expect("Could not change state.");
-        commands.insert_resource(MatchData {
-            result: "Orange won!".to_owned(),
-        });
+/// Shows the round result once it can no longer be rolled back, that is once
+/// its end left the prediction window. The round stays underneath, paused, so
+/// that the series can go on with the same session.
+pub fn check_win(
+    mut state: ResMut<State<AppState>>,
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    rounds: Query<(&RoundTimer, &Series)>,
+    scores: Query<&PlayerScore>,
+    waves: Query<&Waves>,
+    flag_scores: Query<&FlagScore>,
+    hill_scores: Query<&HillScore>,
+) {
+    let (timer, series) = match rounds.iter().next() {
+        Some(round) => round,
+        None => return,
+    };
+    if timer.ended_at == 0 || timer.frame - timer.ended_at < MAX_PREDICTION as u32 {
+        return;
     }
+
+    let mut result = match *mode {
+        GameMode::Survival => match waves.iter().next() {
+            Some(waves) => format!(
+                "Overrun on wave {}!\nFinal score: {}",
+                waves.wave, waves.score
+            ),
+            None => "Overrun!".to_owned(),
+        },
+        GameMode::FreeForAll if timer.winner != NO_TEAM => {
+            let kills = scores
+                .iter()
+                .find(|score| score.handle == timer.winner as usize)
+                .map_or(0, |score| score.kills);
+            format!("Player {} won!\n{} kills", timer.winner + 1, kills)
+        }
+        _ if timer.winner == NO_TEAM => "Draw!".to_owned(),
+        _ => format!("{} won!", team_name(timer.winner)),
+    };
+    if let Some(score) = flag_scores.iter().next() {
+        result += &format!("\n{} - {}", score.captures_a, score.captures_b);
+    }
+    if let Some(score) = hill_scores.iter().next() {
+        result += &format!("\n{} - {}", score.points_a, score.points_b);
+    }
+    if timer.overtime {
+        result += "\nin overtime";
+    }
+
+    let wins = series
+        .wins
+        .iter()
+        .enumerate()
+        .map(|(side, wins)| {
+            let name = match *mode {
+                GameMode::FreeForAll => format!("P{}", side + 1),
+                _ => team_name(side as Team).to_owned(),
+            };
+            (name, *wins)
+        })
+        .collect();
+
+    state.push(AppState::Win).expect("Could not change state.");
+    commands.insert_resource(MatchData {
+        result,
+        scores: ScoreLine::table(scores.iter()),
+        round: series.round,
+        started_at: series.started_at,
+        best_of: series.best_of,
+        wins,
+        series_over: series.is_over(),
+        can_swap: mode.has_teams(),
+    });
 }
 
 pub fn cleanup(query: Query<Entity, With<RoundEntity>>, mut commands: Commands) {
     commands.remove_resource::<FrameCount>();
     commands.remove_resource::<LocalHandles>();
+    commands.remove_resource::<Bots>();
     commands.remove_resource::<P2PSession<GGRSConfig>>();
     commands.remove_resource::<SessionType>();
+    commands.remove_resource::<MatchData>();
+    commands.insert_resource(PauseMenu::default());
+    commands.insert_resource(LeftPlayers::default());
 
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
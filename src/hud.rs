@@ -0,0 +1,699 @@
+use bevy::prelude::*;
+
+use crate::{
+    abilities::Ability,
+    components::{Energy, Health, PlayerEntity, PlayerScore, RoundEntity, SessionEntity},
+    menu::connect::LocalHandles,
+    modes::{
+        ctf::{FlagScore, CAPTURE_LIMIT},
+        ffa::{leader, Respawn, FRAG_LIMIT},
+        koth::{HillScore, SCORE_TO_WIN},
+        survival::Waves,
+        team_name, GameMode, MatchOptions, Team, TEAM_A, TEAM_B,
+    },
+    pickups::{PowerUps, SHIELD_AMOUNT},
+    round::{RoundTimer, Series},
+    ships::{ShipClassId, ShipClasses},
+    weapons::Weapon,
+    FontAssets, BUTTON_TEXT, FPS,
+};
+
+const BAR_WIDTH: f32 = 200.;
+const BAR_HEIGHT: f32 = 14.;
+const BAR_BACKGROUND: Color = Color::rgba(0.1, 0.1, 0.1, 0.8);
+const ENERGY_COLOR: Color = Color::rgb(0.3, 0.7, 1.0);
+const ENERGY_LOW_COLOR: Color = Color::rgb(1.0, 0.4, 0.2);
+const ENERGY_LOW: f32 = 0.25;
+const SHIELD_COLOR: Color = Color::rgb(0.6, 0.9, 1.0);
+const HULL_COLOR: Color = Color::rgb(0.3, 0.9, 0.4);
+const HULL_LOW_COLOR: Color = Color::rgb(0.9, 0.2, 0.2);
+const HULL_LOW: f32 = 0.3;
+const NOTICE_SECS: f32 = 4.;
+const KILL_FEED_SECS: f32 = 5.;
+const KILL_FEED_LINES: usize = 5;
+const FLOATING_BAR_SIZE: (f32, f32) = (50., 5.);
+const FLOATING_BAR_OFFSET: f32 = 45.;
+
+#[derive(Component)]
+pub struct EnergyBar;
+
+#[derive(Component)]
+pub struct ShieldBar;
+
+#[derive(Component)]
+pub struct HullBar;
+
+/// Weapon and ability of the local ship.
+#[derive(Component)]
+pub struct LoadoutText;
+
+/// Ships still flying on each side.
+#[derive(Component)]
+pub struct AliveText;
+
+/// Status line of the current game mode, empty in plain versus.
+#[derive(Component)]
+pub struct ModeText;
+
+#[derive(Component)]
+pub struct RoundTimerText;
+
+/// Short lived message at the top of the screen, like a player leaving.
+#[derive(Component, Default)]
+pub struct NoticeText {
+    remaining: f32,
+}
+
+/// Who destroyed whom lately. Deaths are noticed from the scores rather than
+/// from the rollback systems, a kill undone by a rollback may still show.
+#[derive(Component, Default)]
+pub struct KillFeed {
+    /// Deaths seen so far for each handle.
+    deaths: Vec<u32>,
+    /// Lines shown, with the seconds they have left.
+    lines: Vec<(String, f32)>,
+}
+
+/// Health bar floating over a ship, kept out of its children so that it
+/// doesn't turn with it.
+#[derive(Component)]
+pub struct FloatingBar {
+    ship: Entity,
+}
+
+#[derive(Component)]
+pub struct FloatingBarFill;
+
+#[derive(Component)]
+pub struct HasFloatingBar;
+
+impl NoticeText {
+    pub fn show(&mut self, text: &mut Text, message: &str) {
+        text.sections[0].value = message.to_owned();
+        self.remaining = NOTICE_SECS;
+    }
+}
+
+pub fn setup_hud(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(16.),
+                    bottom: Val::Px(16.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            spawn_bar(parent, &font_assets, "ENERGY", ENERGY_COLOR, EnergyBar);
+            spawn_bar(parent, &font_assets, "SHIELD", SHIELD_COLOR, ShieldBar);
+            spawn_bar(parent, &font_assets, "HULL", HULL_COLOR, HullBar);
+            // weapon and ability, with their cooldowns
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect {
+                            bottom: Val::Px(8.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font_assets.default_font.clone(),
+                            font_size: 20.,
+                            color: BUTTON_TEXT,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(LoadoutText);
+        })
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+
+    // game mode status
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(16.),
+                    left: Val::Px(16.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font_assets.default_font.clone(),
+                    font_size: 24.,
+                    color: BUTTON_TEXT,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(ModeText)
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+
+    // round clock and series score
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(16.),
+                    right: Val::Px(16.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font_assets.default_font.clone(),
+                    font_size: 24.,
+                    color: BUTTON_TEXT,
+                },
+                TextAlignment {
+                    horizontal: HorizontalAlign::Right,
+                    ..Default::default()
+                },
+            ),
+            ..Default::default()
+        })
+        .insert(RoundTimerText)
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+
+    // recent kills, under the clock
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(96.),
+                    right: Val::Px(16.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: font_assets.default_font.clone(),
+                    font_size: 18.,
+                    color: BUTTON_TEXT,
+                },
+                TextAlignment {
+                    horizontal: HorizontalAlign::Right,
+                    ..Default::default()
+                },
+            ),
+            ..Default::default()
+        })
+        .insert(KillFeed::default())
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+
+    spawn_centered_text(&mut commands, &font_assets, 16., AliveText);
+    spawn_centered_text(&mut commands, &font_assets, 64., NoticeText::default());
+}
+
+fn spawn_bar(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    label: &str,
+    color: Color,
+    marker: impl Component,
+) {
+    parent.spawn_bundle(TextBundle {
+        text: Text::with_section(
+            label,
+            TextStyle {
+                font: font_assets.default_font.clone(),
+                font_size: 16.,
+                color: BUTTON_TEXT,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    });
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                ..Default::default()
+            },
+            color: BAR_BACKGROUND.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                        ..Default::default()
+                    },
+                    color: color.into(),
+                    ..Default::default()
+                })
+                .insert(marker);
+        });
+}
+
+/// Text line centered at the top of the screen, the ui has no other way to
+/// center an absolute node than a full width row around it.
+fn spawn_centered_text(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    top: f32,
+    marker: impl Component,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(top),
+                    left: Val::Px(0.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Auto),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font_assets.default_font.clone(),
+                            font_size: 24.,
+                            color: BUTTON_TEXT,
+                        },
+                        TextAlignment {
+                            horizontal: HorizontalAlign::Center,
+                            ..Default::default()
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(marker);
+        })
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+}
+
+fn set_mode_text(texts: &mut Query<&mut Text, With<ModeText>>, label: String) {
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+pub fn update_wave_text(waves: Query<&Waves>, mut texts: Query<&mut Text, With<ModeText>>) {
+    let waves = match waves.iter().next() {
+        Some(waves) => waves,
+        None => return,
+    };
+    let label = if waves.wave == 0 {
+        format!("GET READY  {}", waves.countdown / FPS as u32 + 1)
+    } else {
+        format!("WAVE {}  SCORE {}", waves.wave, waves.score)
+    };
+    set_mode_text(&mut texts, label);
+}
+
+pub fn update_flag_text(scores: Query<&FlagScore>, mut texts: Query<&mut Text, With<ModeText>>) {
+    let score = match scores.iter().next() {
+        Some(score) => score,
+        None => return,
+    };
+    let label = format!(
+        "CAPTURES  BLUE {}  ORANGE {}  (FIRST TO {})",
+        score.captures(TEAM_A),
+        score.captures(TEAM_B),
+        CAPTURE_LIMIT
+    );
+    set_mode_text(&mut texts, label);
+}
+
+pub fn update_hill_text(scores: Query<&HillScore>, mut texts: Query<&mut Text, With<ModeText>>) {
+    let score = match scores.iter().next() {
+        Some(score) => score,
+        None => return,
+    };
+    // shown in seconds of holding a zone
+    let label = format!(
+        "HILL  BLUE {}  ORANGE {}  (FIRST TO {})",
+        score.points(TEAM_A) / FPS as u32,
+        score.points(TEAM_B) / FPS as u32,
+        SCORE_TO_WIN / FPS as u32
+    );
+    set_mode_text(&mut texts, label);
+}
+
+pub fn update_ffa_text(
+    mode: Res<GameMode>,
+    scores: Query<&PlayerScore>,
+    respawns: Query<&Respawn>,
+    players: Query<&PlayerEntity>,
+    local_handles: Res<LocalHandles>,
+    mut texts: Query<&mut Text, With<ModeText>>,
+) {
+    if *mode != GameMode::FreeForAll {
+        return;
+    }
+    let mut label = format!("FRAGS TO {}", FRAG_LIMIT);
+    if let Some(leader) = leader(scores.iter()) {
+        label += &format!("  LEADER P{} ({})", leader.handle + 1, leader.kills);
+    }
+
+    // countdown while the local ship waits to come back
+    let ego = local_handles.handles.first().copied();
+    let respawning = respawns
+        .iter()
+        .find(|respawn| Some(respawn.handle) == ego)
+        .filter(|respawn| !players.iter().any(|player| player.handle == respawn.handle));
+    if let Some(respawn) = respawning {
+        label += &format!("\nRESPAWN IN {}", respawn.countdown / FPS as u32 + 1);
+    }
+    set_mode_text(&mut texts, label);
+}
+
+pub fn update_round_timer_text(
+    mode: Res<GameMode>,
+    options: Res<MatchOptions>,
+    rounds: Query<(&RoundTimer, &Series)>,
+    mut texts: Query<&mut Text, With<RoundTimerText>>,
+) {
+    let (timer, series) = match rounds.iter().next() {
+        Some(round) => round,
+        None => return,
+    };
+
+    let mut label = if timer.overtime {
+        "OVERTIME".to_owned()
+    } else if let Some(frames) = timer.remaining(mode.rules(&options).time_limit) {
+        // round up, the clock reads 0:00 only once the time is out
+        let secs = (frames + FPS as u32 - 1) / FPS as u32;
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        String::new()
+    };
+    if series.best_of > 1 {
+        let wins = match *mode {
+            GameMode::FreeForAll => series
+                .wins
+                .iter()
+                .enumerate()
+                .filter(|(_, wins)| **wins > 0)
+                .map(|(handle, wins)| format!("P{} {}", handle + 1, wins))
+                .collect::<Vec<_>>()
+                .join("  "),
+            _ => format!("{} - {}", series.wins[0], series.wins[1]),
+        };
+        label += &format!("\nROUND {} OF {}  {}", series.round, series.best_of, wins);
+    }
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+pub fn update_energy_bar(
+    players: Query<(&PlayerEntity, &Energy)>,
+    mut bars: Query<(&mut Style, &mut UiColor), With<EnergyBar>>,
+) {
+    let ratio = players
+        .iter()
+        .find(|(player, _)| player.ego)
+        .map(|(_, energy)| energy.value / energy.max)
+        .unwrap_or(0.);
+
+    for (mut style, mut color) in bars.iter_mut() {
+        style.size.width = Val::Percent(ratio * 100.);
+        *color = if ratio < ENERGY_LOW {
+            ENERGY_LOW_COLOR.into()
+        } else {
+            ENERGY_COLOR.into()
+        };
+    }
+}
+
+pub fn update_notice_text(time: Res<Time>, mut notices: Query<(&mut Text, &mut NoticeText)>) {
+    for (mut text, mut notice) in notices.iter_mut() {
+        if notice.remaining > 0. {
+            notice.remaining -= time.delta_seconds();
+            if notice.remaining <= 0. {
+                text.sections[0].value.clear();
+            }
+        }
+    }
+}
+
+pub fn update_hull_bars(
+    players: Query<(&PlayerEntity, &Health, &PowerUps)>,
+    mut hull_bars: Query<(&mut Style, &mut UiColor), (With<HullBar>, Without<ShieldBar>)>,
+    mut shield_bars: Query<&mut Style, With<ShieldBar>>,
+) {
+    let (hull, shield) = players
+        .iter()
+        .find(|(player, _, _)| player.ego)
+        .map(|(_, health, powerups)| (health.hp / health.max, powerups.shield / SHIELD_AMOUNT))
+        .unwrap_or((0., 0.));
+
+    for (mut style, mut color) in hull_bars.iter_mut() {
+        style.size.width = Val::Percent(hull * 100.);
+        *color = if hull < HULL_LOW {
+            HULL_LOW_COLOR.into()
+        } else {
+            HULL_COLOR.into()
+        };
+    }
+    for mut style in shield_bars.iter_mut() {
+        style.size.width = Val::Percent(shield.min(1.) * 100.);
+    }
+}
+
+pub fn update_loadout_text(
+    classes: Res<ShipClasses>,
+    players: Query<(&PlayerEntity, &Weapon, &Ability, &ShipClassId)>,
+    mut texts: Query<&mut Text, With<LoadoutText>>,
+) {
+    let label = match players.iter().find(|(player, _, _, _)| player.ego) {
+        Some((_, weapon, ability, class_id)) => {
+            let stats = weapon.stats();
+            let weapon_status = if weapon.charge > 0 && stats.charge_frames > 0 {
+                format!(
+                    "CHARGING {}%",
+                    (weapon.charge * 100 / stats.charge_frames).min(100)
+                )
+            } else {
+                cooldown_label(weapon.cooldown)
+            };
+            let ability_status = if ability.is_active() {
+                "ACTIVE".to_owned()
+            } else {
+                cooldown_label(ability.cooldown)
+            };
+            format!(
+                "{}  {}\n{}  {}",
+                stats.name.to_uppercase(),
+                weapon_status,
+                classes.get(class_id.id).ability.name().to_uppercase(),
+                ability_status
+            )
+        }
+        None => String::new(),
+    };
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+fn cooldown_label(frames: u32) -> String {
+    if frames == 0 {
+        "READY".to_owned()
+    } else {
+        format!("{:.1}s", frames as f32 / FPS as f32)
+    }
+}
+
+pub fn update_alive_text(
+    mode: Res<GameMode>,
+    players: Query<(&PlayerEntity, &Health)>,
+    mut texts: Query<&mut Text, With<AliveText>>,
+) {
+    let alive = |team: Option<Team>| {
+        players
+            .iter()
+            .filter(|(player, health)| health.hp > 0. && team.map_or(true, |t| player.team == t))
+            .count()
+    };
+    let label = if mode.has_teams() {
+        format!(
+            "{} {}  -  {} {}",
+            team_name(TEAM_A).to_uppercase(),
+            alive(Some(TEAM_A)),
+            alive(Some(TEAM_B)),
+            team_name(TEAM_B).to_uppercase(),
+        )
+    } else {
+        format!("{} ALIVE", alive(None))
+    };
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+pub fn update_kill_feed(
+    time: Res<Time>,
+    scores: Query<&PlayerScore>,
+    mut feeds: Query<(&mut Text, &mut KillFeed)>,
+) {
+    let mut sorted_scores: Vec<_> = scores.iter().collect();
+    sorted_scores.sort_by_key(|score| score.handle);
+
+    for (mut text, mut feed) in feeds.iter_mut() {
+        for score in sorted_scores.iter() {
+            if feed.deaths.len() <= score.handle {
+                feed.deaths.resize(score.handle + 1, 0);
+            }
+            // scores start over with every round
+            let seen = feed.deaths[score.handle];
+            if score.deaths > seen {
+                let line = match score.killer() {
+                    Some(killer) => format!("P{} destroyed P{}", killer + 1, score.handle + 1),
+                    None => format!("P{} was destroyed", score.handle + 1),
+                };
+                feed.lines.push((line, KILL_FEED_SECS));
+            }
+            feed.deaths[score.handle] = score.deaths;
+        }
+
+        let delta = time.delta_seconds();
+        feed.lines.iter_mut().for_each(|(_, left)| *left -= delta);
+        feed.lines.retain(|(_, left)| *left > 0.);
+        let overflow = feed.lines.len().saturating_sub(KILL_FEED_LINES);
+        feed.lines.drain(..overflow);
+
+        let label = feed
+            .lines
+            .iter()
+            .map(|(line, _)| line.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+/// Keeps a health bar over every ship, hidden along with cloaked ones.
+#[allow(clippy::type_complexity)]
+pub fn floating_bar_system(
+    mut commands: Commands,
+    new_ships: Query<Entity, (With<PlayerEntity>, Without<HasFloatingBar>)>,
+    ships: Query<(&Transform, &Health, &Visibility), (With<PlayerEntity>, Without<FloatingBar>)>,
+    mut bars: Query<
+        (
+            Entity,
+            &FloatingBar,
+            &mut Transform,
+            &mut Visibility,
+            &Children,
+        ),
+        Without<PlayerEntity>,
+    >,
+    mut fills: Query<
+        (&mut Transform, &mut Sprite, &mut Visibility),
+        (
+            With<FloatingBarFill>,
+            Without<FloatingBar>,
+            Without<PlayerEntity>,
+        ),
+    >,
+) {
+    for ship in new_ships.iter() {
+        commands.entity(ship).insert(HasFloatingBar);
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform::from_xyz(0., 0., 5.),
+                sprite: Sprite {
+                    color: BAR_BACKGROUND,
+                    custom_size: Some(FLOATING_BAR_SIZE.into()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        transform: Transform::from_xyz(0., 0., 0.1),
+                        sprite: Sprite {
+                            color: HULL_COLOR,
+                            custom_size: Some(FLOATING_BAR_SIZE.into()),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(FloatingBarFill);
+            })
+            .insert(FloatingBar { ship })
+            .insert(RoundEntity);
+    }
+
+    for (entity, bar, mut transform, mut visibility, children) in bars.iter_mut() {
+        let (ship_tf, health, ship_visibility) = match ships.get(bar.ship) {
+            Ok(ship) => ship,
+            Err(_) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+        transform.translation.x = ship_tf.translation.x;
+        transform.translation.y = ship_tf.translation.y + FLOATING_BAR_OFFSET;
+        // visibility isn't inherited, the fill follows by hand
+        visibility.is_visible = ship_visibility.is_visible;
+
+        let ratio = (health.hp / health.max).clamp(0., 1.);
+        for child in children.iter() {
+            if let Ok((mut fill_tf, mut sprite, mut fill_visibility)) = fills.get_mut(*child) {
+                fill_tf.scale.x = ratio;
+                fill_tf.translation.x = -(1. - ratio) * FLOATING_BAR_SIZE.0 / 2.;
+                sprite.color = if ratio < HULL_LOW {
+                    HULL_LOW_COLOR
+                } else {
+                    HULL_COLOR
+                };
+                fill_visibility.is_visible = ship_visibility.is_visible;
+            }
+        }
+    }
+}
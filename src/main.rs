@@ -1,26 +1,89 @@
 #![allow(unused)]
 
+mod abilities;
+mod arrows;
+mod bots;
+mod broadphase;
+mod camera;
 mod checksum;
+mod collision;
 mod components;
+mod controls;
 mod game;
+mod hud;
 mod menu;
+mod minimap;
+mod modes;
+mod pickups;
+mod rng;
 mod rollback_systems;
+mod round;
+mod scoreboard;
+mod ships;
+mod weapons;
 
+use abilities::{ability_system, cloak_visibility_system, shield_burst_system, Ability};
+use arrows::{edge_arrow_spawn_system, edge_arrow_system};
 use bevy::{prelude::*, ecs::system::Resource};
 use bevy_asset_loader::{AssetCollection, AssetLoader};
 use bevy_ggrs::GGRSPlugin;
+use bots::BotSettings;
+use camera::{camera_follow_system, camera_zoom_system, CameraSettings};
 use checksum::{checksum, Checksum};
-use components::{AngularVelocity, FrameCount, Movable, Velocity};
-use game::{check_win, print_p2p_events, setup_camera, setup_round, spawn_players};
+use collision::{
+    hitbox_debug_system, toggle_hitbox_debug, Broadphase, Hitbox, HitboxDebug, BROADPHASE_CELL_SIZE,
+};
+use components::{
+    AngularVelocity, Energy, FrameCount, Health, Laser, Movable, PlayerEntity, PlayerScore,
+    RoundEntity, SessionEntity, ThrustEngine, Velocity,
+};
+use controls::{controller_connection_system, ControllerSettings, Controllers, LocalPlayers};
+use game::{
+    check_win, disconnect_left_players, print_p2p_events, restore_rollback_entities, setup_camera,
+    setup_round, spawn_players, ARENA_SIZE,
+};
 use ggrs::Config;
+use hud::{
+    floating_bar_system, setup_hud, update_alive_text, update_energy_bar, update_ffa_text,
+    update_flag_text, update_hill_text, update_hull_bars, update_kill_feed, update_loadout_text,
+    update_notice_text, update_round_timer_text, update_wave_text,
+};
 use menu::{
     connect::{create_matchbox_socket, update_matchbox_socket},
     online::{update_lobby_btn, update_lobby_id, update_lobby_id_display},
+    pause::PauseMenu,
+};
+use minimap::{
+    minimap_blip_system, setup_minimap, toggle_minimap, update_minimap, MinimapSettings,
 };
+use modes::{
+    ctf::{flag_indicator_system, flag_system, setup_ctf, setup_ctf_visuals, Flag, FlagScore},
+    ffa::{respawn_system, setup_ffa, Respawn},
+    koth::{hill_system, setup_koth, zone_visual_system, ControlZone, HillScore},
+    survival::{
+        invader_ai_system, invader_contact_system, invader_hit_system, setup_survival, wave_system,
+        Invader, Waves,
+    },
+    GameMode, MatchOptions,
+};
+use pickups::{
+    pickup_collect_system, pickup_spawn_system, powerup_tick_system, setup_pickups, Pickup,
+    PickupSpawner, PowerUps,
+};
+use rng::{setup_rng, RollbackRng};
 use rollback_systems::{
-    apply_inputs, camera_system, increase_frame_count, laser_hit_system, movable_system,
-    player_fire_system, explosion_animation_system,
+    apply_inputs, energy_regen_system, explosion_animation_system, increase_frame_count,
+    laser_hit_system, movable_system, player_fire_system, ship_collision_system, update_broadphase,
+};
+use round::{
+    leave_system, round_restarting, round_system, series_system, setup_series, simulation_running,
+    LeftPlayers, NextRoundVote, RoundTimer, Series, SeriesSettings,
 };
+use scoreboard::{setup_scoreboard, toggle_scoreboard, update_scoreboard};
+use ships::{
+    ship_class_system, update_ship_sprites, SelectedShip, ShipClassId, ShipClasses, Steering,
+};
+use weapons::{homing_system, Homing, Weapon};
 
 const PLAYER_SPRITE: &str = "player_a_01.png";
 const PLAYER_SCALE: f32 = 1.2;
@@ -35,6 +98,8 @@ const NUM_PLAYERS: usize = NUM_ALLIES + NUM_ENNEMIES;
 const FPS: usize = 60;
 const ROLLBACK_SYSTEMS: &str = "rollback_systems";
 const CHECKSUM_UPDATE: &str = "checksum_update";
+const ROUND_RESET: &str = "round_reset";
+const ROUND_RESTART: &str = "round_restart";
 const MAX_PREDICTION: usize = 12;
 const INPUT_DELAY: usize = 2;
 const CHECK_DISTANCE: usize = 2;
@@ -65,6 +130,8 @@ pub struct ImageAssets {
 pub struct FontAssets {
     #[asset(path = "fonts/FiraSans-Bold.ttf")]
     pub default_font: Handle<Font>,
+    #[asset(path = "fonts/FiraMono-Medium.ttf")]
+    pub mono_font: Handle<Font>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -80,11 +147,29 @@ pub enum AppState {
 
 #[derive(SystemLabel, Debug, Clone, Hash, Eq, PartialEq)]
 enum SystemLabel {
+    ShipClass,
     Input,
+    Ability,
     ShootInput,
+    Homing,
     Velocity,
+    Broadphase,
+    ShieldBurst,
+    ShipCollision,
+    PickupSpawn,
+    Waves,
+    InvaderAi,
+    InvaderContact,
+    Pickups,
+    LaserHit,
+    InvaderHit,
+    Objectives,
+    Round,
     SpawnPlayers,
     RoundSetup,
+    Rng,
+    Series,
+    SetupPickups,
 }
 
 #[derive(Debug)]
@@ -110,24 +195,167 @@ fn main() {
         .register_rollback_type::<Velocity>()
         .register_rollback_type::<FrameCount>()
         .register_rollback_type::<Checksum>()
+        .register_rollback_type::<Health>()
+        .register_rollback_type::<PlayerScore>()
+        .register_rollback_type::<Weapon>()
+        .register_rollback_type::<Energy>()
+        .register_rollback_type::<ShipClassId>()
+        .register_rollback_type::<Ability>()
+        .register_rollback_type::<Laser>()
+        .register_rollback_type::<Pickup>()
+        .register_rollback_type::<PickupSpawner>()
+        .register_rollback_type::<PowerUps>()
+        .register_rollback_type::<RollbackRng>()
+        .register_rollback_type::<Waves>()
+        .register_rollback_type::<Invader>()
+        .register_rollback_type::<Flag>()
+        .register_rollback_type::<FlagScore>()
+        .register_rollback_type::<ControlZone>()
+        .register_rollback_type::<HillScore>()
+        .register_rollback_type::<RoundTimer>()
+        .register_rollback_type::<Series>()
+        .register_rollback_type::<Respawn>()
+        // what entities despawned in the rollback schedule need to come back,
+        // the rest is restored by `restore_rollback_entities`
+        .register_rollback_type::<Hitbox>()
+        .register_rollback_type::<Movable>()
+        .register_rollback_type::<AngularVelocity>()
+        .register_rollback_type::<ThrustEngine>()
+        .register_rollback_type::<Steering>()
+        .register_rollback_type::<PlayerEntity>()
+        .register_rollback_type::<Homing>()
+        .register_rollback_type::<RoundEntity>()
+        .register_rollback_type::<SessionEntity>()
         .with_rollback_schedule(
             Schedule::default()
                 .with_stage(
+                    ROUND_RESET,
+                    SystemStage::single_threaded()
+                        .with_run_criteria(simulation_running)
+                        .with_system(series_system)
+                        .with_system(leave_system),
+                )
+                // sets the next round of a series up, on the frame the previous one got cleared
+                .with_stage_after(
+                    ROUND_RESET,
+                    ROUND_RESTART,
+                    SystemStage::parallel()
+                        .with_run_criteria(round_restarting)
+                        .with_system(spawn_players.label(SystemLabel::SpawnPlayers))
+                        .with_system(
+                            setup_pickups
+                                .label(SystemLabel::SetupPickups)
+                                .after(SystemLabel::SpawnPlayers),
+                        )
+                        .with_system(setup_survival.after(SystemLabel::SetupPickups))
+                        .with_system(setup_ctf.after(SystemLabel::SetupPickups))
+                        .with_system(setup_koth.after(SystemLabel::SetupPickups))
+                        .with_system(setup_ffa.after(SystemLabel::SetupPickups)),
+                )
+                .with_stage_after(
+                    ROUND_RESTART,
                     ROLLBACK_SYSTEMS,
                     SystemStage::parallel()
-                        .with_system(apply_inputs.label(SystemLabel::Input))
+                        .with_run_criteria(simulation_running)
+                        .with_system(ship_class_system.label(SystemLabel::ShipClass))
+                        .with_system(
+                            apply_inputs
+                                .label(SystemLabel::Input)
+                                .after(SystemLabel::ShipClass),
+                        )
+                        .with_system(
+                            ability_system
+                                .label(SystemLabel::Ability)
+                                .after(SystemLabel::Input),
+                        )
                         .with_system(
                             player_fire_system
                                 .label(SystemLabel::ShootInput)
-                                .after(SystemLabel::Input),
+                                .after(SystemLabel::Ability),
+                        )
+                        .with_system(energy_regen_system.after(SystemLabel::ShootInput))
+                        .with_system(
+                            pickup_spawn_system
+                                .label(SystemLabel::PickupSpawn)
+                                .after(SystemLabel::ShootInput),
+                        )
+                        .with_system(
+                            wave_system
+                                .label(SystemLabel::Waves)
+                                .after(SystemLabel::PickupSpawn),
+                        )
+                        .with_system(
+                            invader_ai_system
+                                .label(SystemLabel::InvaderAi)
+                                .after(SystemLabel::Waves),
+                        )
+                        .with_system(
+                            homing_system
+                                .label(SystemLabel::Homing)
+                                .after(SystemLabel::ShootInput),
                         )
                         .with_system(
                             movable_system
                                 .label(SystemLabel::Velocity)
-                                .after(SystemLabel::ShootInput),
+                                .after(SystemLabel::Homing)
+                                .after(SystemLabel::InvaderAi),
+                        )
+                        .with_system(
+                            update_broadphase
+                                .label(SystemLabel::Broadphase)
+                                .after(SystemLabel::Velocity),
+                        )
+                        .with_system(
+                            shield_burst_system
+                                .label(SystemLabel::ShieldBurst)
+                                .after(SystemLabel::Broadphase),
+                        )
+                        .with_system(
+                            ship_collision_system
+                                .label(SystemLabel::ShipCollision)
+                                .after(SystemLabel::ShieldBurst),
+                        )
+                        .with_system(
+                            invader_contact_system
+                                .label(SystemLabel::InvaderContact)
+                                .after(SystemLabel::ShipCollision),
+                        )
+                        .with_system(
+                            pickup_collect_system
+                                .label(SystemLabel::Pickups)
+                                .after(SystemLabel::InvaderContact),
+                        )
+                        .with_system(
+                            laser_hit_system
+                                .label(SystemLabel::LaserHit)
+                                .after(SystemLabel::Pickups),
+                        )
+                        .with_system(
+                            invader_hit_system
+                                .label(SystemLabel::InvaderHit)
+                                .after(SystemLabel::LaserHit),
+                        )
+                        .with_system(powerup_tick_system.after(SystemLabel::LaserHit))
+                        .with_system(
+                            flag_system
+                                .label(SystemLabel::Objectives)
+                                .after(SystemLabel::InvaderHit),
+                        )
+                        .with_system(
+                            hill_system
+                                .label(SystemLabel::Objectives)
+                                .after(SystemLabel::InvaderHit),
+                        )
+                        .with_system(
+                            respawn_system
+                                .label(SystemLabel::Objectives)
+                                .after(SystemLabel::InvaderHit),
+                        )
+                        .with_system(
+                            round_system
+                                .label(SystemLabel::Round)
+                                .after(SystemLabel::Objectives),
                         )
-                        .with_system(laser_hit_system.after(SystemLabel::Velocity))
-                        .with_system(camera_system)
                         .with_system(increase_frame_count)
                         .with_system(explosion_animation_system)
                         // .with_system(explosion_to_spawn_system),
@@ -142,12 +370,40 @@ fn main() {
 
     app.add_plugins(DefaultPlugins)
         .add_state(AppState::AssetLoading)
-        // main menu
-        .add_system_set(SystemSet::on_enter(AppState::MenuMain).with_system(menu::main::setup_ui))
+        .init_resource::<HitboxDebug>()
+        .insert_resource(Broadphase::new(BROADPHASE_CELL_SIZE, ARENA_SIZE / 2.))
+        .insert_resource(ShipClasses::load())
+        .init_resource::<SelectedShip>()
+        .init_resource::<BotSettings>()
+        .init_resource::<GameMode>()
+        .init_resource::<MatchOptions>()
+        .init_resource::<SeriesSettings>()
+        .init_resource::<NextRoundVote>()
+        .init_resource::<PauseMenu>()
+        .init_resource::<LeftPlayers>()
+        .init_resource::<MinimapSettings>()
+        .init_resource::<CameraSettings>()
+        .init_resource::<LocalPlayers>()
+        .init_resource::<ControllerSettings>()
+        .init_resource::<Controllers>()
+        .add_system(controller_connection_system)
+        // main menu, which also ends the session of the last round
+        .add_system_set(
+            SystemSet::on_enter(AppState::MenuMain)
+                .with_system(game::cleanup)
+                .with_system(menu::main::setup_ui),
+        )
         .add_system_set(
             SystemSet::on_update(AppState::MenuMain)
                 .with_system(menu::main::btn_visuals)
-                .with_system(menu::main::btn_listeners),
+                .with_system(menu::main::btn_listeners)
+                .with_system(menu::main::update_ship_class_text)
+                .with_system(menu::main::update_bot_difficulty_text)
+                .with_system(menu::main::update_game_mode_text)
+                .with_system(menu::main::update_series_text)
+                .with_system(menu::main::update_ram_damage_text)
+                .with_system(menu::main::update_friendly_fire_text)
+                .with_system(menu::main::update_local_players_text),
         )
         .add_system_set(SystemSet::on_exit(AppState::MenuMain).with_system(menu::main::cleanup_ui))
         //online menu
@@ -160,7 +416,9 @@ fn main() {
                 .with_system(update_lobby_id_display)
                 .with_system(update_lobby_btn)
                 .with_system(menu::online::btn_visuals)
-                .with_system(menu::online::btn_listeners),
+                .with_system(menu::online::btn_listeners)
+                .with_system(menu::online::update_online_bots_text)
+                .with_system(menu::main::update_ship_class_text),
         )
         .add_system_set(
             SystemSet::on_exit(AppState::MenuOnline).with_system(menu::online::cleanup_ui),
@@ -183,11 +441,19 @@ fn main() {
                 .with_system(menu::connect::cleanup_ui),
         )
         // win menu
-        .add_system_set(SystemSet::on_enter(AppState::Win).with_system(menu::win::setup_ui))
+        .add_system_set(
+            SystemSet::on_enter(AppState::Win)
+                .with_system(menu::win::setup_ui)
+                .with_system(menu::pause::close_pause_menu),
+        )
         .add_system_set(
             SystemSet::on_update(AppState::Win)
                 .with_system(menu::win::btn_visuals)
-                .with_system(menu::win::btn_listeners),
+                .with_system(menu::win::btn_listeners)
+                .with_system(menu::win::next_round_system)
+                .with_system(menu::pause::leave_match_system)
+                .with_system(disconnect_left_players)
+                .with_system(restore_rollback_entities),
         )
         .add_system_set(SystemSet::on_exit(AppState::Win).with_system(menu::win::cleanup_ui))
         // local round
@@ -199,10 +465,67 @@ fn main() {
                         .label(SystemLabel::SpawnPlayers)
                         .after(SystemLabel::RoundSetup),
                 )
-                .with_system(setup_camera.after(SystemLabel::RoundSetup)),
+                .with_system(setup_camera.after(SystemLabel::RoundSetup))
+                .with_system(
+                    setup_rng
+                        .label(SystemLabel::Rng)
+                        .after(SystemLabel::SpawnPlayers),
+                )
+                .with_system(
+                    setup_series
+                        .label(SystemLabel::Series)
+                        .after(SystemLabel::Rng),
+                )
+                .with_system(
+                    setup_pickups
+                        .label(SystemLabel::SetupPickups)
+                        .after(SystemLabel::Series),
+                )
+                .with_system(setup_survival.after(SystemLabel::SetupPickups))
+                .with_system(setup_ctf.after(SystemLabel::SetupPickups))
+                .with_system(setup_ctf_visuals)
+                .with_system(setup_koth.after(SystemLabel::SetupPickups))
+                .with_system(setup_ffa.after(SystemLabel::SetupPickups))
+                .with_system(setup_scoreboard)
+                .with_system(setup_hud)
+                .with_system(setup_minimap),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::RoundLocal)
+                .with_system(menu::pause::toggle_pause_menu)
+                .with_system(menu::pause::update_pause_ui)
+                .with_system(menu::pause::btn_visuals)
+                .with_system(menu::pause::btn_listeners)
+                .with_system(check_win)
+                .with_system(toggle_scoreboard)
+                .with_system(update_scoreboard)
+                .with_system(toggle_hitbox_debug)
+                .with_system(hitbox_debug_system)
+                .with_system(update_energy_bar)
+                .with_system(update_hull_bars)
+                .with_system(update_loadout_text)
+                .with_system(update_alive_text)
+                .with_system(update_kill_feed)
+                .with_system(floating_bar_system)
+                .with_system(toggle_minimap)
+                .with_system(minimap_blip_system)
+                .with_system(update_minimap)
+                .with_system(edge_arrow_spawn_system)
+                .with_system(edge_arrow_system)
+                .with_system(camera_zoom_system)
+                .with_system(camera_follow_system)
+                .with_system(update_wave_text)
+                .with_system(update_flag_text)
+                .with_system(flag_indicator_system)
+                .with_system(update_hill_text)
+                .with_system(zone_visual_system)
+                .with_system(update_ffa_text)
+                .with_system(update_round_timer_text)
+                .with_system(update_notice_text)
+                .with_system(update_ship_sprites)
+                .with_system(restore_rollback_entities)
+                .with_system(cloak_visibility_system),
         )
-        .add_system_set(SystemSet::on_update(AppState::RoundLocal).with_system(check_win))
-        .add_system_set(SystemSet::on_exit(AppState::RoundLocal).with_system(game::cleanup))
         // online round
         .add_system_set(
             SystemSet::on_enter(AppState::RoundOnline)
@@ -212,14 +535,70 @@ fn main() {
                         .label(SystemLabel::SpawnPlayers)
                         .after(SystemLabel::RoundSetup),
                 )
-                .with_system(setup_camera.after(SystemLabel::SpawnPlayers)),
+                .with_system(setup_camera.after(SystemLabel::SpawnPlayers))
+                .with_system(
+                    setup_rng
+                        .label(SystemLabel::Rng)
+                        .after(SystemLabel::SpawnPlayers),
+                )
+                .with_system(
+                    setup_series
+                        .label(SystemLabel::Series)
+                        .after(SystemLabel::Rng),
+                )
+                .with_system(
+                    setup_pickups
+                        .label(SystemLabel::SetupPickups)
+                        .after(SystemLabel::Series),
+                )
+                .with_system(setup_survival.after(SystemLabel::SetupPickups))
+                .with_system(setup_ctf.after(SystemLabel::SetupPickups))
+                .with_system(setup_ctf_visuals)
+                .with_system(setup_koth.after(SystemLabel::SetupPickups))
+                .with_system(setup_ffa.after(SystemLabel::SetupPickups))
+                .with_system(setup_scoreboard)
+                .with_system(setup_hud)
+                .with_system(setup_minimap),
         )
         .add_system_set(
             SystemSet::on_update(AppState::RoundOnline)
+                .with_system(menu::pause::toggle_pause_menu)
+                .with_system(menu::pause::update_pause_ui)
+                .with_system(menu::pause::btn_visuals)
+                .with_system(menu::pause::btn_listeners)
                 .with_system(print_p2p_events)
-                .with_system(check_win),
-        )
-        .add_system_set(SystemSet::on_exit(AppState::RoundOnline).with_system(game::cleanup));
+                .with_system(menu::pause::leave_match_system)
+                .with_system(disconnect_left_players)
+                .with_system(check_win)
+                .with_system(toggle_scoreboard)
+                .with_system(update_scoreboard)
+                .with_system(toggle_hitbox_debug)
+                .with_system(hitbox_debug_system)
+                .with_system(update_energy_bar)
+                .with_system(update_hull_bars)
+                .with_system(update_loadout_text)
+                .with_system(update_alive_text)
+                .with_system(update_kill_feed)
+                .with_system(floating_bar_system)
+                .with_system(toggle_minimap)
+                .with_system(minimap_blip_system)
+                .with_system(update_minimap)
+                .with_system(edge_arrow_spawn_system)
+                .with_system(edge_arrow_system)
+                .with_system(camera_zoom_system)
+                .with_system(camera_follow_system)
+                .with_system(update_wave_text)
+                .with_system(update_flag_text)
+                .with_system(flag_indicator_system)
+                .with_system(update_hill_text)
+                .with_system(zone_visual_system)
+                .with_system(update_ffa_text)
+                .with_system(update_round_timer_text)
+                .with_system(update_notice_text)
+                .with_system(update_ship_sprites)
+                .with_system(restore_rollback_entities)
+                .with_system(cloak_visibility_system),
+        );
 
     #[cfg(target_arch = "wasm32")]
     {
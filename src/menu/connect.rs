@@ -4,8 +4,11 @@ use ggrs::{PlayerHandle, PlayerType, SessionBuilder};
 use matchbox_socket::WebRtcSocket;
 
 use crate::{
+    bots::{BotSettings, Bots},
+    modes::GameMode,
+    rng::SessionSeed,
     AppState, FontAssets, GGRSConfig, BUTTON_TEXT, FPS, HOVERED_BUTTON, INPUT_DELAY,
-    MAX_PREDICTION, NORMAL_BUTTON, NUM_PLAYERS, PRESSED_BUTTON,
+    MAX_PREDICTION, NORMAL_BUTTON, PRESSED_BUTTON,
 };
 
 const MATCHBOX_ADDR: &str = "wss://match.gschup.dev";
@@ -43,13 +46,15 @@ pub fn update_matchbox_socket(
     commands: Commands,
     mut state: ResMut<State<AppState>>,
     mut socket_res: ResMut<Option<WebRtcSocket>>,
+    bot_settings: Res<BotSettings>,
+    mode: Res<GameMode>,
 ) {
     if let Some(socket) = socket_res.as_mut() {
         socket.accept_new_connections();
-        if socket.players().len() >= NUM_PLAYERS {
+        if socket.players().len() >= mode.num_players() - bot_settings.online_bots {
             // take the socket
             let socket = socket_res.as_mut().take().unwrap();
-            create_ggrs_session(commands, socket);
+            create_ggrs_session(commands, socket, &bot_settings, mode.num_players());
             state
                 .set(AppState::RoundOnline)
                 .expect("Could not change state.");
@@ -180,18 +185,30 @@ pub fn cleanup_ui(query: Query<Entity, With<MenuConnectUI>>, mut commands: Comma
     }
 }
 
-fn create_ggrs_session(mut commands: Commands, socket: WebRtcSocket) {
+fn create_ggrs_session(
+    mut commands: Commands,
+    socket: WebRtcSocket,
+    bot_settings: &BotSettings,
+    num_players: usize,
+) {
     // create a new ggrs session
     let mut sess_build = SessionBuilder::<GGRSConfig>::new()
-        .with_num_players(NUM_PLAYERS)
+        .with_num_players(num_players)
         .with_max_prediction_window(MAX_PREDICTION)
         .with_fps(FPS)
         .expect("Invalid FPS")
         .with_input_delay(INPUT_DELAY);
 
+    // every peer knows all ids, hashing them gives the same seed everywhere
+    let seed = SessionSeed::from_peer_ids(socket.players().iter().map(|player| match player {
+        PlayerType::Local => socket.id().as_str(),
+        PlayerType::Remote(id) | PlayerType::Spectator(id) => id.as_str(),
+    }));
+
     // add players
+    let players = socket.players();
     let mut handles = Vec::new();
-    for (i, player_type) in socket.players().iter().enumerate() {
+    for (i, player_type) in players.iter().enumerate() {
         if *player_type == PlayerType::Local {
             handles.push(i);
         }
@@ -200,6 +217,33 @@ fn create_ggrs_session(mut commands: Commands, socket: WebRtcSocket) {
             .expect("Invalid player added.");
     }
 
+    // bots take the remaining handles and run on the peer with the lowest id
+    let host = players
+        .iter()
+        .filter_map(|player| match player {
+            PlayerType::Local => Some(socket.id().clone()),
+            PlayerType::Remote(id) => Some(id.clone()),
+            PlayerType::Spectator(_) => None,
+        })
+        .min()
+        .expect("No players in session.");
+    let mut bots = Bots {
+        handles: Vec::new(),
+        difficulty: bot_settings.difficulty.unwrap_or_default(),
+    };
+    for i in players.len()..num_players {
+        let player_type = if host == *socket.id() {
+            handles.push(i);
+            bots.handles.push(i);
+            PlayerType::Local
+        } else {
+            PlayerType::Remote(host.clone())
+        };
+        sess_build = sess_build
+            .add_player(player_type, i)
+            .expect("Invalid bot added.");
+    }
+
     // start the GGRS session
     let sess = sess_build
         .start_p2p_session(socket)
@@ -207,5 +251,7 @@ fn create_ggrs_session(mut commands: Commands, socket: WebRtcSocket) {
 
     commands.insert_resource(sess);
     commands.insert_resource(LocalHandles { handles });
+    commands.insert_resource(bots);
+    commands.insert_resource(seed);
     commands.insert_resource(SessionType::P2PSession);
 }
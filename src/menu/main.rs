@@ -3,8 +3,14 @@ use bevy_ggrs::SessionType;
 use ggrs::{PlayerType, SessionBuilder};
 
 use crate::{
+    bots::{BotDifficulty, BotSettings, Bots},
+    controls::{Controllers, LocalPlayers},
+    modes::{GameMode, MatchOptions},
+    rng::SessionSeed,
+    round::SeriesSettings,
+    ships::{SelectedShip, ShipClasses},
     AppState, FontAssets, GGRSConfig, ImageAssets, BUTTON_TEXT, CHECK_DISTANCE, FPS,
-    HOVERED_BUTTON, INPUT_DELAY, MAX_PREDICTION, NORMAL_BUTTON, NUM_PLAYERS, PRESSED_BUTTON,
+    HOVERED_BUTTON, INPUT_DELAY, MAX_PREDICTION, NORMAL_BUTTON, PRESSED_BUTTON,
 };
 
 use super::connect::LocalHandles;
@@ -16,9 +22,37 @@ pub struct MenuMainUI;
 pub enum MenuMainBtn {
     OnlineMatch,
     LocalMatch,
+    ShipClass,
+    Bots,
+    Mode,
+    Series,
+    RamDamage,
+    FriendlyFire,
+    LocalPlayers,
     Quit,
 }
 
+#[derive(Component)]
+pub struct ShipClassText;
+
+#[derive(Component)]
+pub struct BotDifficultyText;
+
+#[derive(Component)]
+pub struct GameModeText;
+
+#[derive(Component)]
+pub struct SeriesText;
+
+#[derive(Component)]
+pub struct RamDamageText;
+
+#[derive(Component)]
+pub struct FriendlyFireText;
+
+#[derive(Component)]
+pub struct LocalPlayersText;
+
 pub fn setup_ui(
     mut commands: Commands,
     image_assets: Res<ImageAssets>,
@@ -118,6 +152,230 @@ pub fn setup_ui(
                 })
                 .insert(MenuMainBtn::LocalMatch);
 
+            // ship class button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(ShipClassText);
+                })
+                .insert(MenuMainBtn::ShipClass);
+
+            // bots button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(BotDifficultyText);
+                })
+                .insert(MenuMainBtn::Bots);
+
+            // game mode button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(GameModeText);
+                })
+                .insert(MenuMainBtn::Mode);
+
+            // series length button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(SeriesText);
+                })
+                .insert(MenuMainBtn::Series);
+
+            // ram damage button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(RamDamageText);
+                })
+                .insert(MenuMainBtn::RamDamage);
+
+            // friendly fire button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(FriendlyFireText);
+                })
+                .insert(MenuMainBtn::FriendlyFire);
+
+            // players sharing this machine button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(350.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(LocalPlayersText);
+                })
+                .insert(MenuMainBtn::LocalPlayers);
+
             // quit button
             parent
                 .spawn_bundle(ButtonBundle {
@@ -172,10 +430,118 @@ pub fn btn_visuals(
     }
 }
 
+pub fn update_ship_class_text(
+    selected_ship: Res<SelectedShip>,
+    classes: Res<ShipClasses>,
+    mut query: Query<&mut Text, With<ShipClassText>>,
+) {
+    for mut text in query.iter_mut() {
+        let label = format!("Ship: {}", classes.get(selected_ship.0).name);
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+pub fn update_bot_difficulty_text(
+    bot_settings: Res<BotSettings>,
+    mut query: Query<&mut Text, With<BotDifficultyText>>,
+) {
+    for mut text in query.iter_mut() {
+        let label = match bot_settings.difficulty {
+            Some(difficulty) => format!("Bots: {}", difficulty.name()),
+            None => "Bots: Off".to_owned(),
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+pub fn update_game_mode_text(mode: Res<GameMode>, mut query: Query<&mut Text, With<GameModeText>>) {
+    for mut text in query.iter_mut() {
+        let label = format!("Mode: {}", mode.name());
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+pub fn update_series_text(
+    settings: Res<SeriesSettings>,
+    mut query: Query<&mut Text, With<SeriesText>>,
+) {
+    for mut text in query.iter_mut() {
+        let label = format!("Best of {}", settings.best_of);
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+pub fn update_ram_damage_text(
+    options: Res<MatchOptions>,
+    mut query: Query<&mut Text, With<RamDamageText>>,
+) {
+    for mut text in query.iter_mut() {
+        let label = if options.ram_damage {
+            "Ram damage: On"
+        } else {
+            "Ram damage: Off"
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label.to_owned();
+        }
+    }
+}
+
+pub fn update_friendly_fire_text(
+    options: Res<MatchOptions>,
+    mode: Res<GameMode>,
+    mut query: Query<&mut Text, With<FriendlyFireText>>,
+) {
+    for mut text in query.iter_mut() {
+        let label = if !mode.has_teams() {
+            "Friendly fire: -"
+        } else if options.friendly_fire {
+            "Friendly fire: On"
+        } else {
+            "Friendly fire: Off"
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label.to_owned();
+        }
+    }
+}
+
+pub fn update_local_players_text(
+    local_players: Res<LocalPlayers>,
+    controllers: Res<Controllers>,
+    mut query: Query<&mut Text, With<LocalPlayersText>>,
+) {
+    for mut text in query.iter_mut() {
+        // controllers come and go while in the menu too
+        let label = match controllers.count() {
+            0 => format!("Local players: {}", local_players.count),
+            pads => format!("Local players: {} ({} pads)", local_players.count, pads),
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
 pub fn btn_listeners(
     mut exit: EventWriter<AppExit>,
     mut commands: Commands,
     mut state: ResMut<State<AppState>>,
+    mut selected_ship: ResMut<SelectedShip>,
+    mut bot_settings: ResMut<BotSettings>,
+    mut mode: ResMut<GameMode>,
+    mut series_settings: ResMut<SeriesSettings>,
+    mut local_players: ResMut<LocalPlayers>,
+    mut options: ResMut<MatchOptions>,
+    classes: Res<ShipClasses>,
     mut interaction_query: Query<(&Interaction, &MenuMainBtn), Changed<Interaction>>,
 ) {
     for (interaction, btn) in interaction_query.iter_mut() {
@@ -187,11 +553,35 @@ pub fn btn_listeners(
                         .expect("Could not change state.");
                 }
                 MenuMainBtn::LocalMatch => {
-                    create_synctest_session(&mut commands);
+                    create_synctest_session(&mut commands, &bot_settings, &local_players, *mode);
                     state
                         .set(AppState::RoundLocal)
                         .expect("Could not change state.");
                 }
+                MenuMainBtn::ShipClass => {
+                    selected_ship.0 = (selected_ship.0 + 1) % classes.0.len() as u8;
+                }
+                MenuMainBtn::Bots => {
+                    bot_settings.difficulty = BotDifficulty::cycle(bot_settings.difficulty);
+                }
+                MenuMainBtn::Mode => {
+                    *mode = mode.next();
+                    // keep at least the host human in online matches
+                    bot_settings.online_bots = bot_settings.online_bots.min(mode.num_players() - 1);
+                    local_players.clamp(mode.num_players());
+                }
+                MenuMainBtn::Series => {
+                    series_settings.cycle();
+                }
+                MenuMainBtn::RamDamage => {
+                    options.ram_damage = !options.ram_damage;
+                }
+                MenuMainBtn::FriendlyFire => {
+                    options.friendly_fire = !options.friendly_fire;
+                }
+                MenuMainBtn::LocalPlayers => {
+                    local_players.cycle(mode.num_players());
+                }
                 MenuMainBtn::Quit => {
                     exit.send(AppExit);
                 }
@@ -206,16 +596,22 @@ pub fn cleanup_ui(query: Query<Entity, With<MenuMainUI>>, mut commands: Commands
     }
 }
 
-fn create_synctest_session(commands: &mut Commands) {
+fn create_synctest_session(
+    commands: &mut Commands,
+    bot_settings: &BotSettings,
+    local_players: &LocalPlayers,
+    mode: GameMode,
+) {
+    let num_players = mode.num_players();
     let mut sess_build = SessionBuilder::<GGRSConfig>::new()
-        .with_num_players(NUM_PLAYERS)
+        .with_num_players(num_players)
         .with_max_prediction_window(MAX_PREDICTION)
         .with_fps(FPS)
         .expect("Invalid FPS")
         .with_input_delay(INPUT_DELAY)
         .with_check_distance(CHECK_DISTANCE);
 
-    for i in 0..NUM_PLAYERS {
+    for i in 0..num_players {
         sess_build = sess_build
             .add_player(PlayerType::Local, i)
             .expect("Could not add local player");
@@ -225,7 +621,16 @@ fn create_synctest_session(commands: &mut Commands) {
 
     commands.insert_resource(sess);
     commands.insert_resource(SessionType::SyncTestSession);
+    commands.insert_resource(SessionSeed(rand::random()));
     commands.insert_resource(LocalHandles {
-        handles: (0..NUM_PLAYERS).collect(),
+        handles: (0..num_players).collect(),
+    });
+    // the people at the keyboard take the first handles, with bots on they take the others
+    commands.insert_resource(match bot_settings.difficulty {
+        Some(difficulty) => Bots {
+            handles: (local_players.count..num_players).collect(),
+            difficulty,
+        },
+        None => Bots::default(),
     });
 }
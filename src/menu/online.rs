@@ -1,11 +1,15 @@
 use bevy::prelude::*;
 
 use crate::{
-    AppState, FontAssets, BUTTON_TEXT, DISABLED_BUTTON, HOVERED_BUTTON, NORMAL_BUTTON, NUM_PLAYERS,
+    bots::BotSettings,
+    modes::{GameMode, MatchOptions},
+    round::SeriesSettings,
+    ships::{SelectedShip, ShipClasses},
+    AppState, FontAssets, BUTTON_TEXT, DISABLED_BUTTON, HOVERED_BUTTON, NORMAL_BUTTON,
     PRESSED_BUTTON,
 };
 
-use super::connect::ConnectData;
+use super::{connect::ConnectData, main::ShipClassText};
 
 #[derive(Component)]
 pub struct MenuOnlineUI;
@@ -14,6 +18,8 @@ pub struct MenuOnlineUI;
 pub enum MenuOnlineBtn {
     LobbyMatch,
     QuickMatch,
+    ShipClass,
+    Bots,
     Back,
 }
 
@@ -23,6 +29,9 @@ pub struct ButtonEnabled(bool);
 #[derive(Component)]
 pub struct LobbyCodeText;
 
+#[derive(Component)]
+pub struct OnlineBotsText;
+
 pub struct LobbyID(String);
 
 pub fn setup_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
@@ -144,6 +153,70 @@ pub fn setup_ui(mut commands: Commands, font_assets: Res<FontAssets>) {
                 })
                 .insert(MenuOnlineBtn::QuickMatch);
 
+            // ship class button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(ShipClassText);
+                })
+                .insert(MenuOnlineBtn::ShipClass);
+
+            // bot slots button
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(16.)),
+                        padding: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: NORMAL_BUTTON.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: font_assets.default_font.clone(),
+                                    font_size: 40.0,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(OnlineBotsText);
+                })
+                .insert(MenuOnlineBtn::Bots);
+
             // back button
             parent
                 .spawn_bundle(ButtonBundle {
@@ -221,6 +294,18 @@ pub fn update_lobby_btn(
     }
 }
 
+pub fn update_online_bots_text(
+    bot_settings: Res<BotSettings>,
+    mut query: Query<&mut Text, With<OnlineBotsText>>,
+) {
+    for mut text in query.iter_mut() {
+        let label = format!("Bots: {}", bot_settings.online_bots);
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
 pub fn btn_visuals(
     mut interaction_query: Query<
         (&Interaction, &mut UiColor, Option<&ButtonEnabled>),
@@ -254,6 +339,12 @@ pub fn btn_listeners(
     mut commands: Commands,
     mut state: ResMut<State<AppState>>,
     lobby_id: Res<LobbyID>,
+    mut selected_ship: ResMut<SelectedShip>,
+    mut bot_settings: ResMut<BotSettings>,
+    mode: Res<GameMode>,
+    series_settings: Res<SeriesSettings>,
+    options: Res<MatchOptions>,
+    classes: Res<ShipClasses>,
     mut interaction_query: Query<
         (&Interaction, &MenuOnlineBtn, Option<&ButtonEnabled>),
         Changed<Interaction>,
@@ -273,7 +364,13 @@ pub fn btn_listeners(
             match btn {
                 MenuOnlineBtn::LobbyMatch => {
                     commands.insert_resource(ConnectData {
-                        lobby_id: format!("bevy{}?next={}", lobby_id.0, NUM_PLAYERS),
+                        lobby_id: room_id(
+                            &lobby_id.0,
+                            *mode,
+                            series_settings.best_of,
+                            bot_settings.online_bots,
+                            &options,
+                        ),
                     });
                     state
                         .set(AppState::MenuConnect)
@@ -281,12 +378,25 @@ pub fn btn_listeners(
                 }
                 MenuOnlineBtn::QuickMatch => {
                     commands.insert_resource(ConnectData {
-                        lobby_id: format!("bevy?next={}", NUM_PLAYERS).to_owned(),
+                        lobby_id: room_id(
+                            "",
+                            *mode,
+                            series_settings.best_of,
+                            bot_settings.online_bots,
+                            &options,
+                        ),
                     });
                     state
                         .set(AppState::MenuConnect)
                         .expect("Could not change state.");
                 }
+                MenuOnlineBtn::ShipClass => {
+                    selected_ship.0 = (selected_ship.0 + 1) % classes.0.len() as u8;
+                }
+                MenuOnlineBtn::Bots => {
+                    // every handle but the host's can go to a bot
+                    bot_settings.online_bots = (bot_settings.online_bots + 1) % mode.num_players();
+                }
                 MenuOnlineBtn::Back => {
                     state
                         .set(AppState::MenuMain)
@@ -297,6 +407,35 @@ pub fn btn_listeners(
     }
 }
 
+/// Matchbox room for the given lobby. Peers only meet others playing the same
+/// mode, series length and options and expecting the same number of bots, and
+/// the room fills up with the remaining humans.
+fn room_id(
+    lobby_id: &str,
+    mode: GameMode,
+    best_of: u32,
+    bots: usize,
+    options: &MatchOptions,
+) -> String {
+    let mut room = format!("bevy{}", lobby_id);
+    if mode != GameMode::Versus {
+        room.push_str(&format!("-{}", mode.tag()));
+    }
+    if best_of > 1 {
+        room.push_str(&format!("-bo{}", best_of));
+    }
+    if !options.ram_damage {
+        room.push_str("-noram");
+    }
+    if options.friendly_fire && mode.has_teams() {
+        room.push_str("-ff");
+    }
+    if bots > 0 {
+        room.push_str(&format!("-bots{}", bots));
+    }
+    format!("{}?next={}", room, mode.num_players() - bots)
+}
+
 pub fn cleanup_ui(query: Query<Entity, With<MenuOnlineUI>>, mut commands: Commands) {
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
@@ -0,0 +1,336 @@
+use bevy::prelude::*;
+
+use crate::{
+    camera::CameraSettings,
+    collision::HitboxDebug,
+    components::{RoundEntity, SessionEntity},
+    controls::{ControllerSettings, Controllers},
+    minimap::MinimapSettings,
+    AppState, FontAssets, BUTTON_TEXT, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON,
+};
+
+const PAUSE_KEY: KeyCode = KeyCode::Escape;
+/// Time the leaving flag goes out with the inputs before the session is
+/// dropped, for the other peers to get it confirmed.
+const LEAVE_SECS: f32 = 1.;
+const OVERLAY_COLOR: Color = Color::rgba(0., 0., 0., 0.6);
+
+/// State of the escape menu. A local game holds still while it is open, an
+/// online one goes on with the local ship left idle.
+#[derive(Default)]
+pub struct PauseMenu {
+    pub open: bool,
+    /// Showing the settings rather than the main page.
+    pub settings: bool,
+    /// Seconds left before the session is dropped, once we left an online match.
+    pub leaving: Option<f32>,
+}
+
+impl PauseMenu {
+    /// Leaves the match. A local one ends right away. Online, the other peers
+    /// get told through the inputs first, so that they drop our ships at once
+    /// rather than when our connection times out.
+    pub fn leave(&mut self, state: &mut State<AppState>, online: bool) {
+        if online {
+            self.open = false;
+            self.settings = false;
+            self.leaving.get_or_insert(LEAVE_SECS);
+        } else {
+            state
+                .replace(AppState::MenuMain)
+                .expect("Could not change state.");
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PauseUI;
+
+#[derive(Component)]
+pub enum MenuPauseBtn {
+    Resume,
+    Settings,
+    Hitboxes,
+    ViewRadius,
+    MinimapSize,
+    MinimapZoom,
+    FogOfWar,
+    Deadzone,
+    FirstControllerSeat,
+    Back,
+    Leave,
+}
+
+pub fn toggle_pause_menu(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    controllers: Res<Controllers>,
+    mut pause_menu: ResMut<PauseMenu>,
+) {
+    let start = controllers
+        .pads()
+        .any(|pad| gamepad_buttons.just_pressed(GamepadButton(pad, GamepadButtonType::Start)));
+    if keyboard_input.just_pressed(PAUSE_KEY) || start {
+        if pause_menu.settings {
+            pause_menu.settings = false;
+        } else {
+            pause_menu.open = !pause_menu.open;
+        }
+    }
+}
+
+/// The round was decided while the menu was open, the win screen takes over.
+/// Leaving goes on.
+pub fn close_pause_menu(
+    mut commands: Commands,
+    mut pause_menu: ResMut<PauseMenu>,
+    query: Query<Entity, With<PauseUI>>,
+) {
+    pause_menu.open = false;
+    pause_menu.settings = false;
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+}
+
+/// Spawns the page of the menu that is open, on every change of the menu or of
+/// the settings it shows.
+#[allow(clippy::too_many_arguments)]
+pub fn update_pause_ui(
+    mut commands: Commands,
+    pause_menu: Res<PauseMenu>,
+    state: Res<State<AppState>>,
+    font_assets: Res<FontAssets>,
+    debug: Res<HitboxDebug>,
+    minimap: Res<MinimapSettings>,
+    camera: Res<CameraSettings>,
+    controller_settings: Res<ControllerSettings>,
+    controllers: Res<Controllers>,
+    query: Query<Entity, With<PauseUI>>,
+) {
+    if !pause_menu.is_changed()
+        && !debug.is_changed()
+        && !minimap.is_changed()
+        && !camera.is_changed()
+        && !controller_settings.is_changed()
+        && !controllers.is_changed()
+    {
+        return;
+    }
+    for e in query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    if !pause_menu.open {
+        return;
+    }
+
+    let title = if pause_menu.settings {
+        "Settings"
+    } else if *state.current() == AppState::RoundLocal {
+        "Paused"
+    } else {
+        "Menu - the match goes on"
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_content: AlignContent::Center,
+                align_items: AlignItems::Center,
+                align_self: AlignSelf::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: OVERLAY_COLOR.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::Center,
+                    margin: Rect::all(Val::Px(16.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    title,
+                    TextStyle {
+                        font: font_assets.default_font.clone(),
+                        font_size: 64.,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            if pause_menu.settings {
+                let hitboxes = if debug.0 {
+                    "Hitboxes: On"
+                } else {
+                    "Hitboxes: Off"
+                };
+                spawn_button(parent, &font_assets, hitboxes, MenuPauseBtn::Hitboxes);
+                let view_radius = format!("View radius: {}", camera.view_radius);
+                spawn_button(parent, &font_assets, &view_radius, MenuPauseBtn::ViewRadius);
+                let minimap_size = if minimap.size > 0. {
+                    format!("Minimap: {}px", minimap.size)
+                } else {
+                    "Minimap: Off".to_owned()
+                };
+                spawn_button(
+                    parent,
+                    &font_assets,
+                    &minimap_size,
+                    MenuPauseBtn::MinimapSize,
+                );
+                let minimap_zoom = format!("Minimap zoom: x{}", minimap.zoom);
+                spawn_button(
+                    parent,
+                    &font_assets,
+                    &minimap_zoom,
+                    MenuPauseBtn::MinimapZoom,
+                );
+                // lifting the fog online would show what the other players can't see
+                if *state.current() == AppState::RoundLocal {
+                    let fog = if minimap.fog_of_war {
+                        "Fog of war: On"
+                    } else {
+                        "Fog of war: Off"
+                    };
+                    spawn_button(parent, &font_assets, fog, MenuPauseBtn::FogOfWar);
+                }
+                let deadzone = format!(
+                    "Stick deadzone: {}%",
+                    (controller_settings.deadzone * 100.).round()
+                );
+                spawn_button(parent, &font_assets, &deadzone, MenuPauseBtn::Deadzone);
+                let first_seat = format!("Pads from player {}", controllers.first_seat + 1);
+                spawn_button(
+                    parent,
+                    &font_assets,
+                    &first_seat,
+                    MenuPauseBtn::FirstControllerSeat,
+                );
+                spawn_button(parent, &font_assets, "Back", MenuPauseBtn::Back);
+            } else {
+                spawn_button(parent, &font_assets, "Resume", MenuPauseBtn::Resume);
+                spawn_button(parent, &font_assets, "Settings", MenuPauseBtn::Settings);
+                spawn_button(parent, &font_assets, "Leave Match", MenuPauseBtn::Leave);
+            }
+        })
+        .insert(PauseUI)
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    label: &str,
+    btn: MenuPauseBtn,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(350.0), Val::Px(65.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                margin: Rect::all(Val::Px(16.)),
+                padding: Rect::all(Val::Px(16.)),
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font: font_assets.default_font.clone(),
+                        font_size: 40.0,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        })
+        .insert(btn);
+}
+
+/// Drops the session once the other peers had time to learn that we left.
+pub fn leave_match_system(
+    time: Res<Time>,
+    mut pause_menu: ResMut<PauseMenu>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let remaining = match pause_menu.leaving {
+        Some(remaining) => remaining - time.delta_seconds(),
+        None => return,
+    };
+    if remaining > 0. {
+        pause_menu.leaving = Some(remaining);
+    } else {
+        // the main menu ends the session, dropping the socket with it
+        state
+            .replace(AppState::MenuMain)
+            .expect("Could not change state.");
+    }
+}
+
+pub fn btn_visuals(
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<MenuPauseBtn>),
+    >,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON.into();
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn btn_listeners(
+    mut state: ResMut<State<AppState>>,
+    mut pause_menu: ResMut<PauseMenu>,
+    mut debug: ResMut<HitboxDebug>,
+    mut minimap: ResMut<MinimapSettings>,
+    mut camera: ResMut<CameraSettings>,
+    mut controller_settings: ResMut<ControllerSettings>,
+    mut controllers: ResMut<Controllers>,
+    interaction_query: Query<(&Interaction, &MenuPauseBtn), Changed<Interaction>>,
+) {
+    for (interaction, btn) in interaction_query.iter() {
+        if let Interaction::Clicked = *interaction {
+            match btn {
+                MenuPauseBtn::Resume => pause_menu.open = false,
+                MenuPauseBtn::Settings => pause_menu.settings = true,
+                MenuPauseBtn::Hitboxes => debug.0 = !debug.0,
+                MenuPauseBtn::ViewRadius => camera.cycle_view_radius(),
+                MenuPauseBtn::MinimapSize => minimap.cycle_size(),
+                MenuPauseBtn::MinimapZoom => minimap.cycle_zoom(),
+                MenuPauseBtn::FogOfWar => minimap.fog_of_war = !minimap.fog_of_war,
+                MenuPauseBtn::Deadzone => controller_settings.cycle_deadzone(),
+                MenuPauseBtn::FirstControllerSeat => controllers.cycle_first_seat(),
+                MenuPauseBtn::Back => pause_menu.settings = false,
+                MenuPauseBtn::Leave => {
+                    let online = *state.current() == AppState::RoundOnline;
+                    pause_menu.leave(&mut state, online);
+                }
+            }
+        }
+    }
+}
@@ -1,26 +1,136 @@
 use bevy::prelude::*;
+use ggrs::P2PSession;
 
-use crate::{AppState, FontAssets, BUTTON_TEXT, HOVERED_BUTTON, NORMAL_BUTTON, PRESSED_BUTTON};
+use super::pause::PauseMenu;
+
+use crate::{
+    components::PlayerScore,
+    modes::{Team, TEAM_A, TEAM_B},
+    round::{NextRoundVote, Series},
+    AppState, FontAssets, GGRSConfig, BUTTON_TEXT, HOVERED_BUTTON, MAX_PREDICTION, NORMAL_BUTTON,
+    PRESSED_BUTTON,
+};
+
+const OVERLAY_COLOR: Color = Color::rgba(0., 0., 0., 0.7);
 
 #[derive(Component)]
 pub struct WinUI;
 
 #[derive(Component)]
 pub enum MenuWinBtn {
+    NextRound,
+    Rematch,
+    RematchSwap,
     Back,
 }
 
 pub struct MatchData {
     pub result: String,
+    pub scores: Vec<ScoreLine>,
+    /// Round that just ended, starting at 1.
+    pub round: u32,
+    /// Frame of the series at which that round started, to notice the next one.
+    pub started_at: u32,
+    pub best_of: u32,
+    /// Rounds won so far by each team, or by each player without teams.
+    pub wins: Vec<(String, u32)>,
+    pub series_over: bool,
+    /// The mode has two teams that can trade sides on a rematch.
+    pub can_swap: bool,
 }
 
-pub fn setup_ui(mut commands: Commands, match_data: Res<MatchData>, font_assets: Res<FontAssets>) {
-    // ui camera
-    commands
-        .spawn_bundle(UiCameraBundle::default())
-        .insert(WinUI);
+impl MatchData {
+    /// One line summary of the series, empty for a single round.
+    pub fn series_line(&self) -> String {
+        if self.best_of <= 1 {
+            return String::new();
+        }
+        let wins: Vec<String> = self
+            .wins
+            .iter()
+            .filter(|(_, wins)| *wins > 0 || self.wins.len() == 2)
+            .map(|(name, wins)| format!("{} {}", name, wins))
+            .collect();
+        let status = if self.series_over {
+            "Series over"
+        } else {
+            "Best of"
+        };
+        format!(
+            "Round {} - {} {} - {}",
+            self.round,
+            status,
+            self.best_of,
+            wins.join("  ")
+        )
+    }
+}
+
+/// Snapshot of a [`PlayerScore`] taken when the round ends.
+#[derive(Debug, Clone)]
+pub struct ScoreLine {
+    pub handle: usize,
+    pub team: Team,
+    pub kills: u32,
+    pub deaths: u32,
+    pub damage_dealt: f32,
+    pub accuracy: f32,
+}
+
+impl ScoreLine {
+    pub const HEADER: &'static str = "PLAYER  TEAM    K    D     DMG   ACC";
+
+    /// Builds the score table, grouped by team and sorted by kills.
+    pub fn table<'a>(scores: impl Iterator<Item = &'a PlayerScore>) -> Vec<ScoreLine> {
+        let mut lines: Vec<ScoreLine> = scores
+            .map(|score| ScoreLine {
+                handle: score.handle,
+                team: score.team,
+                kills: score.kills,
+                deaths: score.deaths,
+                damage_dealt: score.damage_dealt,
+                accuracy: score.accuracy(),
+            })
+            .collect();
+        lines.sort_by(|a, b| {
+            a.team
+                .cmp(&b.team)
+                .then(b.kills.cmp(&a.kills))
+                .then(a.deaths.cmp(&b.deaths))
+                .then(a.handle.cmp(&b.handle))
+        });
+        lines
+    }
+
+    pub fn row(&self) -> String {
+        format!(
+            "P{:<5}  {:<4} {:>4} {:>4} {:>7.0} {:>4.0}%",
+            self.handle + 1,
+            match self.team {
+                TEAM_A => "A",
+                TEAM_B => "B",
+                _ => "-",
+            },
+            self.kills,
+            self.deaths,
+            self.damage_dealt,
+            self.accuracy * 100.,
+        )
+    }
+
+    /// Renders the whole table, header included, as a single block of text.
+    pub fn format_table(lines: &[ScoreLine]) -> String {
+        let mut text = ScoreLine::HEADER.to_owned();
+        for line in lines {
+            text.push('\n');
+            text.push_str(&line.row());
+        }
+        text
+    }
+}
 
-    // root node
+pub fn setup_ui(mut commands: Commands, match_data: Res<MatchData>, font_assets: Res<FontAssets>) {
+    // root node, overlaid on the paused round whose ui camera is still around
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -33,7 +143,7 @@ pub fn setup_ui(mut commands: Commands, match_data: Res<MatchData>, font_assets:
                 justify_content: JustifyContent::Center,
                 ..Default::default()
             },
-            color: Color::NONE.into(),
+            color: OVERLAY_COLOR.into(),
             ..Default::default()
         })
         .with_children(|parent| {
@@ -55,6 +165,56 @@ pub fn setup_ui(mut commands: Commands, match_data: Res<MatchData>, font_assets:
                 ),
                 ..Default::default()
             });
+            // series standing
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::Center,
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    match_data.series_line(),
+                    TextStyle {
+                        font: font_assets.default_font.clone(),
+                        font_size: 32.,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            // score table
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    align_self: AlignSelf::Center,
+                    margin: Rect::all(Val::Px(16.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    ScoreLine::format_table(&match_data.scores),
+                    TextStyle {
+                        font: font_assets.mono_font.clone(),
+                        font_size: 24.,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            // votes for what comes next: the next round while the series goes
+            // on, a rematch once it is over
+            if match_data.series_over {
+                spawn_vote_button(parent, &font_assets, "Rematch", MenuWinBtn::Rematch);
+                if match_data.can_swap {
+                    spawn_vote_button(
+                        parent,
+                        &font_assets,
+                        "Rematch, Swap Sides",
+                        MenuWinBtn::RematchSwap,
+                    );
+                }
+            } else {
+                spawn_vote_button(parent, &font_assets, "Next Round", MenuWinBtn::NextRound);
+            }
             // back to menu button
             parent
                 .spawn_bundle(ButtonBundle {
@@ -86,8 +246,42 @@ pub fn setup_ui(mut commands: Commands, match_data: Res<MatchData>, font_assets:
                 .insert(MenuWinBtn::Back);
         })
         .insert(WinUI);
+}
 
-    commands.remove_resource::<MatchData>();
+fn spawn_vote_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    label: &str,
+    btn: MenuWinBtn,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(450.0), Val::Px(65.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                margin: Rect::all(Val::Px(16.)),
+                padding: Rect::all(Val::Px(16.)),
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font: font_assets.default_font.clone(),
+                        font_size: 40.0,
+                        color: BUTTON_TEXT,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        })
+        .insert(btn);
 }
 
 pub fn btn_visuals(
@@ -113,22 +307,56 @@ pub fn btn_visuals(
 
 pub fn btn_listeners(
     mut state: ResMut<State<AppState>>,
-    mut interaction_query: Query<(&Interaction, &MenuWinBtn), Changed<Interaction>>,
+    mut vote: ResMut<NextRoundVote>,
+    mut pause_menu: ResMut<PauseMenu>,
+    session: Option<Res<P2PSession<GGRSConfig>>>,
+    mut interaction_query: Query<(&Interaction, &MenuWinBtn, &Children), Changed<Interaction>>,
+    mut texts: Query<&mut Text>,
 ) {
-    for (interaction, btn) in interaction_query.iter_mut() {
+    for (interaction, btn, children) in interaction_query.iter_mut() {
         if let Interaction::Clicked = *interaction {
             match btn {
+                MenuWinBtn::NextRound | MenuWinBtn::Rematch | MenuWinBtn::RematchSwap => {
+                    // the vote goes out with the inputs, the round starts once everyone agreed
+                    vote.ready = true;
+                    vote.swap_sides = matches!(btn, MenuWinBtn::RematchSwap);
+                    for child in children.iter() {
+                        if let Ok(mut text) = texts.get_mut(*child) {
+                            text.sections[0].value = "Waiting for players...".to_owned();
+                        }
+                    }
+                }
                 MenuWinBtn::Back => {
-                    state
-                        .set(AppState::MenuMain)
-                        .expect("Could not change state.");
+                    // drops the paused round too, the session ends with it
+                    pause_menu.leave(&mut state, session.is_some());
                 }
             }
         }
     }
 }
 
-pub fn cleanup_ui(query: Query<Entity, With<WinUI>>, mut commands: Commands) {
+/// Goes back to the paused round once the next one, or the rematch, started on every peer,
+/// that is once its start left the prediction window.
+pub fn next_round_system(
+    mut state: ResMut<State<AppState>>,
+    match_data: Res<MatchData>,
+    series: Query<&Series>,
+) {
+    for series in series.iter() {
+        if series.started_at != match_data.started_at
+            && series.frame - series.started_at >= MAX_PREDICTION as u32
+        {
+            state.pop().expect("Could not change state.");
+        }
+    }
+}
+
+pub fn cleanup_ui(
+    query: Query<Entity, With<WinUI>>,
+    mut commands: Commands,
+    mut vote: ResMut<NextRoundVote>,
+) {
+    *vote = NextRoundVote::default();
     for e in query.iter() {
         commands.entity(e).despawn_recursive();
     }
@@ -0,0 +1,359 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{PlayerEntity, PlayerScore, RoundEntity, SessionEntity},
+    game::ARENA_SIZE,
+    menu::connect::LocalHandles,
+    modes::{
+        are_enemies, ctf::Flag, koth::ControlZone, player_color, survival::Invader, team_color,
+        Team, NO_TEAM, TEAM_A, TEAM_B,
+    },
+    AppState,
+};
+
+const MINIMAP_KEY: KeyCode = KeyCode::M;
+const MINIMAP_ZOOM_KEY: KeyCode = KeyCode::N;
+/// Side of the minimap in pixels, 0 hides it.
+const MINIMAP_SIZES: [f32; 3] = [160., 240., 0.];
+/// Arena widths shown across the minimap, zoomed in views follow the ego ship.
+const MINIMAP_ZOOMS: [f32; 3] = [1., 2., 4.];
+/// How far a ship sees enemies through the fog of war.
+const VISION_RADIUS: f32 = 600.;
+const MAP_BACKGROUND: Color = Color::rgba(0., 0., 0., 0.6);
+const ARENA_COLOR: Color = Color::rgba(1., 1., 1., 0.08);
+const EGO_COLOR: Color = Color::WHITE;
+const NEUTRAL_COLOR: Color = Color::GRAY;
+const SHIP_BLIP: f32 = 6.;
+const INVADER_BLIP: f32 = 4.;
+const FLAG_BLIP: f32 = 8.;
+
+pub struct MinimapSettings {
+    pub size: f32,
+    pub zoom: f32,
+    /// Enemies only show within sight of the local team. Always on online.
+    pub fog_of_war: bool,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            size: MINIMAP_SIZES[0],
+            zoom: MINIMAP_ZOOMS[0],
+            fog_of_war: true,
+        }
+    }
+}
+
+impl MinimapSettings {
+    pub fn cycle_size(&mut self) {
+        self.size = next_in(&MINIMAP_SIZES, self.size);
+    }
+
+    pub fn cycle_zoom(&mut self) {
+        self.zoom = next_in(&MINIMAP_ZOOMS, self.zoom);
+    }
+}
+
+fn next_in(values: &[f32], current: f32) -> f32 {
+    let index = values
+        .iter()
+        .position(|value| *value == current)
+        .unwrap_or(0);
+    values[(index + 1) % values.len()]
+}
+
+#[derive(Component)]
+pub struct MinimapUI;
+
+/// Part of the minimap covered by the arena.
+#[derive(Component)]
+pub struct MinimapArena;
+
+#[derive(Component)]
+pub struct MinimapBlip {
+    target: Entity,
+}
+
+#[derive(Component)]
+pub struct HasMinimapBlip;
+
+pub fn setup_minimap(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(16.),
+                    bottom: Val::Px(16.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: MAP_BACKGROUND.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..Default::default()
+                    },
+                    color: ARENA_COLOR.into(),
+                    ..Default::default()
+                })
+                .insert(MinimapArena);
+        })
+        .insert(MinimapUI)
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+}
+
+pub fn toggle_minimap(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<MinimapSettings>) {
+    if keyboard_input.just_pressed(MINIMAP_KEY) {
+        settings.cycle_size();
+    }
+    if keyboard_input.just_pressed(MINIMAP_ZOOM_KEY) {
+        settings.cycle_zoom();
+    }
+}
+
+/// Gives every ship, invader and objective a blip on the minimap.
+#[allow(clippy::type_complexity)]
+pub fn minimap_blip_system(
+    mut commands: Commands,
+    minimaps: Query<Entity, With<MinimapUI>>,
+    targets: Query<
+        Entity,
+        (
+            Or<(
+                With<PlayerEntity>,
+                With<Invader>,
+                With<Flag>,
+                With<ControlZone>,
+            )>,
+            Without<HasMinimapBlip>,
+        ),
+    >,
+) {
+    let minimap = match minimaps.iter().next() {
+        Some(minimap) => minimap,
+        None => return,
+    };
+    for target in targets.iter() {
+        commands.entity(target).insert(HasMinimapBlip);
+        commands.entity(minimap).with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(MinimapBlip { target });
+        });
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_minimap(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    state: Res<State<AppState>>,
+    local_handles: Res<LocalHandles>,
+    scores: Query<&PlayerScore>,
+    mut minimaps: Query<&mut Style, (With<MinimapUI>, Without<MinimapArena>, Without<MinimapBlip>)>,
+    mut arenas: Query<&mut Style, (With<MinimapArena>, Without<MinimapBlip>)>,
+    mut blips: Query<(Entity, &MinimapBlip, &mut Style, &mut UiColor)>,
+    targets: Query<(
+        &Transform,
+        Option<&PlayerEntity>,
+        Option<&Visibility>,
+        Option<&Invader>,
+        Option<&Flag>,
+        Option<&ControlZone>,
+    )>,
+) {
+    let size = settings.size;
+    for mut style in minimaps.iter_mut() {
+        style.display = if size > 0. {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        style.size = Size::new(Val::Px(size), Val::Px(size));
+    }
+    if size <= 0. {
+        return;
+    }
+
+    let vision = Vision::new(
+        &settings,
+        &state,
+        &local_handles,
+        scores.iter(),
+        targets
+            .iter()
+            .filter_map(|(transform, player, _, _, _, _)| player.map(|player| (player, transform))),
+    );
+    let ego_position = targets
+        .iter()
+        .find(|(_, player, _, _, _, _)| player.map_or(false, |player| player.ego))
+        .map(|(transform, _, _, _, _, _)| transform.translation.truncate());
+
+    // world area shown, the whole arena or a zoomed in view around the ego ship
+    let extent = ARENA_SIZE / settings.zoom;
+    let center = if settings.zoom > 1. {
+        ego_position.unwrap_or(Vec2::ZERO)
+    } else {
+        Vec2::ZERO
+    };
+    let view_min = center - Vec2::splat(extent / 2.);
+    let to_map = |position: Vec2| (position - view_min) / extent * size;
+
+    for mut style in arenas.iter_mut() {
+        let min = to_map(Vec2::splat(-ARENA_SIZE / 2.)).max(Vec2::ZERO);
+        let max = to_map(Vec2::splat(ARENA_SIZE / 2.)).min(Vec2::splat(size));
+        style.position = Rect {
+            left: Val::Px(min.x),
+            bottom: Val::Px(min.y),
+            ..Default::default()
+        };
+        style.size = Size::new(
+            Val::Px((max.x - min.x).max(0.)),
+            Val::Px((max.y - min.y).max(0.)),
+        );
+    }
+
+    for (entity, blip, mut style, mut color) in blips.iter_mut() {
+        let (transform, player, visibility, invader, flag, zone) = match targets.get(blip.target) {
+            Ok(target) => target,
+            Err(_) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+        let position = transform.translation.truncate();
+
+        let (blip_size, blip_color, visible) = if let Some(player) = player {
+            let color = if player.ego {
+                EGO_COLOR
+            } else {
+                side_color(player.team, player.handle)
+            };
+            (
+                SHIP_BLIP,
+                color,
+                vision.shows_player(player, position, visibility),
+            )
+        } else if invader.is_some() {
+            (
+                INVADER_BLIP,
+                team_color(TEAM_B),
+                vision.sees(position, visibility),
+            )
+        } else if let Some(flag) = flag {
+            (FLAG_BLIP, team_color(flag.team), true)
+        } else if let Some(zone) = zone {
+            (zone.radius * 2. / extent * size, zone_color(zone), true)
+        } else {
+            continue;
+        };
+
+        let spot = to_map(position);
+        let inside = spot.cmpge(Vec2::ZERO).all() && spot.cmple(Vec2::splat(size)).all();
+        style.display = if visible && inside {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        style.size = Size::new(Val::Px(blip_size), Val::Px(blip_size));
+        style.position = Rect {
+            left: Val::Px(spot.x - blip_size / 2.),
+            bottom: Val::Px(spot.y - blip_size / 2.),
+            ..Default::default()
+        };
+        *color = blip_color.into();
+    }
+}
+
+/// Team colour, or the player's own one without teams.
+pub fn side_color(team: Team, handle: usize) -> Color {
+    if team == NO_TEAM {
+        player_color(handle)
+    } else {
+        team_color(team)
+    }
+}
+
+/// Colour of the team holding a control zone, grey while neutral.
+pub fn zone_color(zone: &ControlZone) -> Color {
+    [TEAM_A, TEAM_B]
+        .into_iter()
+        .find(|team| zone.owned_by(*team))
+        .map_or(NEUTRAL_COLOR, team_color)
+}
+
+/// What the local team can see, shared by the minimap and the edge arrows.
+pub struct Vision {
+    ego_handle: Option<usize>,
+    ego_team: Team,
+    fog_of_war: bool,
+    /// Ships looking out for the local team.
+    lookouts: Vec<Vec2>,
+}
+
+impl Vision {
+    pub fn new<'a>(
+        settings: &MinimapSettings,
+        state: &State<AppState>,
+        local_handles: &LocalHandles,
+        mut scores: impl Iterator<Item = &'a PlayerScore>,
+        ships: impl Iterator<Item = (&'a PlayerEntity, &'a Transform)>,
+    ) -> Self {
+        // the local team keeps its side when its ships are down
+        let ego_handle = local_handles.handles.first().copied();
+        let ego_team = scores
+            .find(|score| Some(score.handle) == ego_handle)
+            .map_or(NO_TEAM, |score| score.team);
+        let mut vision = Vision {
+            ego_handle,
+            ego_team,
+            fog_of_war: settings.fog_of_war || *state.current() != AppState::RoundLocal,
+            lookouts: Vec::new(),
+        };
+        vision.lookouts = ships
+            .filter(|(player, _)| vision.friendly(player))
+            .map(|(_, transform)| transform.translation.truncate())
+            .collect();
+        vision
+    }
+
+    pub fn friendly(&self, player: &PlayerEntity) -> bool {
+        Some(player.handle) == self.ego_handle
+            || (self.ego_team != NO_TEAM && player.team == self.ego_team)
+    }
+
+    /// Something hostile at this position is in sight, unless it is cloaked.
+    pub fn sees(&self, position: Vec2, visibility: Option<&Visibility>) -> bool {
+        let hidden = visibility.map_or(false, |visibility| !visibility.is_visible);
+        !hidden
+            && (!self.fog_of_war
+                || self
+                    .lookouts
+                    .iter()
+                    .any(|lookout| lookout.distance(position) <= VISION_RADIUS))
+    }
+
+    /// Teammates always show, enemies when in sight.
+    pub fn shows_player(
+        &self,
+        player: &PlayerEntity,
+        position: Vec2,
+        visibility: Option<&Visibility>,
+    ) -> bool {
+        let enemy = !self.friendly(player) && are_enemies(self.ego_team, player.team);
+        !enemy || self.sees(position, visibility)
+    }
+}
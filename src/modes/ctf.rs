@@ -0,0 +1,311 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::{
+    components::{Health, PlayerEntity, RoundEntity, SessionEntity},
+    game::ARENA_SIZE,
+};
+
+use super::{team_color, GameMode, Team, TEAM_A, TEAM_B};
+
+/// Captures needed to win the match.
+pub const CAPTURE_LIMIT: u32 = 3;
+/// Thrust of a ship carrying a flag, relative to its normal thrust.
+pub const CARRIER_THRUST_FACTOR: f32 = 0.7;
+
+/// Distance of each base from the middle of the arena, along the x axis.
+const BASE_DISTANCE: f32 = ARENA_SIZE * 0.4;
+const BASE_SIZE: f32 = 160.;
+/// Distance under which a ship grabs, returns or captures a flag.
+const FLAG_REACH: f32 = 60.;
+/// Frames a dropped flag stays on the ground before going back to its base.
+const FLAG_RETURN_FRAMES: u32 = 600;
+const FLAG_SIZE: (f32, f32) = (24., 36.);
+/// Distance of the flag indicators from the ship they surround.
+const INDICATOR_DISTANCE: f32 = 90.;
+
+/// Base of the given team, where its flag rests and where it scores.
+pub fn base_position(team: Team) -> Vec2 {
+    // team A spawns on the right side of the arena
+    if team == TEAM_B {
+        Vec2::new(-BASE_DISTANCE, 0.)
+    } else {
+        Vec2::new(BASE_DISTANCE, 0.)
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Flag {
+    pub team: Team,
+    /// Handle of the carrying player plus one, 0 when nobody holds the flag.
+    pub carrier: u8,
+    pub at_base: bool,
+    /// Frames left before a dropped flag returns to its base.
+    pub return_countdown: u32,
+}
+
+impl Flag {
+    pub fn carried_by(&self, handle: usize) -> bool {
+        self.carrier as usize == handle + 1
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct FlagScore {
+    pub captures_a: u32,
+    pub captures_b: u32,
+    /// Frames simulated since the round started.
+    pub frame: u32,
+    /// Frame at which a team reached the capture limit, 0 until then.
+    pub won_at: u32,
+}
+
+impl FlagScore {
+    pub fn captures(&self, team: Team) -> u32 {
+        if team == TEAM_B {
+            self.captures_b
+        } else {
+            self.captures_a
+        }
+    }
+
+    /// Team that reached the capture limit, if any.
+    pub fn winner(&self) -> Option<Team> {
+        if self.captures_a >= CAPTURE_LIMIT {
+            Some(TEAM_A)
+        } else if self.captures_b >= CAPTURE_LIMIT {
+            Some(TEAM_B)
+        } else {
+            None
+        }
+    }
+}
+
+/// Arrow around the local ship pointing at a team's flag.
+#[derive(Component)]
+pub struct FlagIndicator {
+    pub team: Team,
+}
+
+pub fn setup_ctf(mut commands: Commands, mut rip: ResMut<RollbackIdProvider>, mode: Res<GameMode>) {
+    if *mode != GameMode::CaptureTheFlag {
+        return;
+    }
+
+    commands
+        .spawn()
+        .insert(FlagScore::default())
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity);
+
+    for team in [TEAM_A, TEAM_B] {
+        let base = base_position(team);
+        let flag = Flag {
+            team,
+            carrier: 0,
+            at_base: true,
+            return_countdown: 0,
+        };
+        commands
+            .spawn_bundle(flag_bundle(
+                &flag,
+                Transform::from_translation(Vec3::new(base.x, base.y, 2.5)),
+            ))
+            .insert(flag)
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+    }
+}
+
+/// Spawns the bases and the flag indicators. They don't change from one round
+/// to the next, so they stay for the whole session instead of being spawned
+/// again with the flags.
+pub fn setup_ctf_visuals(mut commands: Commands, mode: Res<GameMode>) {
+    if *mode != GameMode::CaptureTheFlag {
+        return;
+    }
+
+    for team in [TEAM_A, TEAM_B] {
+        let base = base_position(team);
+        let color = team_color(team);
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(base.x, base.y, 1.5)),
+                sprite: Sprite {
+                    color: Color::rgba(color.r(), color.g(), color.b(), 0.25),
+                    custom_size: Some(Vec2::splat(BASE_SIZE)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(RoundEntity)
+            .insert(SessionEntity);
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(0., 0., 4.)),
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(10., 22.)),
+                    ..Default::default()
+                },
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert(FlagIndicator { team })
+            .insert(RoundEntity)
+            .insert(SessionEntity);
+    }
+}
+
+pub fn flag_bundle(flag: &Flag, transform: Transform) -> SpriteBundle {
+    SpriteBundle {
+        transform,
+        sprite: Sprite {
+            color: team_color(flag.team),
+            custom_size: Some(Vec2::new(FLAG_SIZE.0, FLAG_SIZE.1)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Applies the flag rules: grab, carry, drop, return and capture.
+pub fn flag_system(
+    mut flags: Query<(&mut Flag, &mut Transform), Without<PlayerEntity>>,
+    mut scores: Query<&mut FlagScore>,
+    players: Query<(&Transform, &PlayerEntity, &Health), Without<Flag>>,
+) {
+    let mut score = match scores.iter_mut().next() {
+        Some(score) => score,
+        None => return,
+    };
+    score.frame += 1;
+
+    // ships in handle order so every peer settles ties the same way
+    let mut ships: Vec<(usize, Team, Vec2)> = players
+        .iter()
+        .filter(|(_, _, health)| health.hp > 0.)
+        .map(|(tf, player, _)| (player.handle, player.team, tf.translation.xy()))
+        .collect();
+    ships.sort_by_key(|(handle, _, _)| *handle);
+
+    let mut sorted_flags: Vec<_> = flags.iter_mut().collect();
+    sorted_flags.sort_by_key(|(flag, _)| flag.team);
+
+    // whether each team's flag sits at its base, needed to capture
+    let mut home = [false; 2];
+    for (flag, _) in sorted_flags.iter() {
+        home[flag.team as usize] = flag.at_base;
+    }
+
+    for (flag, transform) in sorted_flags.iter_mut() {
+        let base = base_position(flag.team);
+
+        if flag.carrier > 0 {
+            let carrier = ships
+                .iter()
+                .find(|(handle, _, _)| flag.carried_by(*handle))
+                .copied();
+            match carrier {
+                Some((_, carrier_team, position)) => {
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+
+                    // scoring requires the carrier's own flag to be home
+                    let own_base = base_position(carrier_team);
+                    if home[carrier_team as usize]
+                        && position.distance(own_base) < BASE_SIZE / 2.
+                        && score.won_at == 0
+                    {
+                        if carrier_team == TEAM_B {
+                            score.captures_b += 1;
+                        } else {
+                            score.captures_a += 1;
+                        }
+                        if score.winner().is_some() {
+                            score.won_at = score.frame;
+                        }
+                        reset_flag(flag, transform, base);
+                    }
+                }
+                None => {
+                    // the carrier was destroyed, the flag falls where it was
+                    flag.carrier = 0;
+                    flag.return_countdown = FLAG_RETURN_FRAMES;
+                }
+            }
+            continue;
+        }
+
+        let position = transform.translation.xy();
+        let toucher = ships
+            .iter()
+            .find(|(_, _, ship)| ship.distance(position) < FLAG_REACH)
+            .copied();
+        match toucher {
+            Some((handle, team, _)) if team != flag.team => {
+                flag.carrier = handle as u8 + 1;
+                flag.at_base = false;
+                home[flag.team as usize] = false;
+            }
+            Some(_) if !flag.at_base => {
+                reset_flag(flag, transform, base);
+                home[flag.team as usize] = true;
+            }
+            _ if !flag.at_base => {
+                if flag.return_countdown > 0 {
+                    flag.return_countdown -= 1;
+                } else {
+                    reset_flag(flag, transform, base);
+                    home[flag.team as usize] = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn reset_flag(flag: &mut Flag, transform: &mut Transform, base: Vec2) {
+    flag.carrier = 0;
+    flag.at_base = true;
+    flag.return_countdown = 0;
+    transform.translation.x = base.x;
+    transform.translation.y = base.y;
+}
+
+/// Places the arrows around the local ship towards each flag.
+pub fn flag_indicator_system(
+    flags: Query<(&Flag, &Transform), Without<FlagIndicator>>,
+    players: Query<(&PlayerEntity, &Transform), Without<FlagIndicator>>,
+    mut indicators: Query<(&FlagIndicator, &mut Transform, &mut Visibility)>,
+) {
+    let ego = players
+        .iter()
+        .find(|(player, _)| player.ego)
+        .map(|(_, tf)| tf.translation.xy());
+
+    for (indicator, mut transform, mut visibility) in indicators.iter_mut() {
+        let flag = flags
+            .iter()
+            .find(|(flag, _)| flag.team == indicator.team)
+            .map(|(_, tf)| tf.translation.xy());
+        let (ego, flag) = match (ego, flag) {
+            (Some(ego), Some(flag)) if ego.distance(flag) > INDICATOR_DISTANCE => (ego, flag),
+            _ => {
+                visibility.is_visible = false;
+                continue;
+            }
+        };
+
+        let dir = (flag - ego).normalize();
+        let position = ego + dir * INDICATOR_DISTANCE;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        transform.rotation = Quat::from_rotation_z(dir.y.atan2(dir.x) - 90_f32.to_radians());
+        visibility.is_visible = true;
+    }
+}
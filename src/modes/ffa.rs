@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::{
+    components::{PlayerEntity, PlayerScore, RoundEntity},
+    game::{spawn_location, spawn_ship},
+    menu::connect::LocalHandles,
+    rng::RollbackRng,
+    round::{RoundTimer, Series},
+    ships::ShipClasses,
+    ImageAssets,
+};
+
+use super::{GameMode, MatchOptions, NO_TEAM};
+
+/// Players in a free-for-all match, empty slots are meant for bots.
+pub const FFA_PLAYERS: usize = 8;
+pub const FRAG_LIMIT: u32 = 10;
+pub const RESPAWN_SECS: u32 = 3;
+
+/// Player with the most kills, fewer deaths then the lowest handle breaking ties.
+pub fn leader<'a>(scores: impl Iterator<Item = &'a PlayerScore>) -> Option<&'a PlayerScore> {
+    scores.min_by_key(|score| (std::cmp::Reverse(score.kills), score.deaths, score.handle))
+}
+
+/// Countdown before a destroyed player comes back.
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Respawn {
+    pub handle: usize,
+    pub countdown: u32,
+}
+
+pub fn setup_ffa(mut commands: Commands, mut rip: ResMut<RollbackIdProvider>, mode: Res<GameMode>) {
+    if *mode != GameMode::FreeForAll {
+        return;
+    }
+
+    for handle in 0..mode.num_players() {
+        commands
+            .spawn()
+            .insert(Respawn {
+                handle,
+                countdown: 0,
+            })
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+    }
+}
+
+/// Brings destroyed ships back until the round is decided.
+#[allow(clippy::too_many_arguments)]
+pub fn respawn_system(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    game_textures: Res<ImageAssets>,
+    classes: Res<ShipClasses>,
+    local_handles: Res<LocalHandles>,
+    mode: Res<GameMode>,
+    options: Res<MatchOptions>,
+    timers: Query<&RoundTimer>,
+    mut respawns: Query<&mut Respawn>,
+    mut rngs: Query<&mut RollbackRng>,
+    players: Query<&PlayerEntity>,
+    series: Query<&Series>,
+) {
+    if timers.iter().any(|timer| timer.ended_at > 0) {
+        return;
+    }
+
+    let rules = mode.rules(&options);
+    let num_players = mode.num_players();
+    let mut sorted_respawns: Vec<_> = respawns.iter_mut().collect();
+    sorted_respawns.sort_by_key(|respawn| respawn.handle);
+
+    let series = series.iter().next();
+    for respawn in sorted_respawns.iter_mut() {
+        if series.map_or(false, |series| series.has_left(respawn.handle)) {
+            continue;
+        }
+        if players.iter().any(|player| player.handle == respawn.handle) {
+            respawn.countdown = rules.respawn_frames.unwrap_or(0);
+            continue;
+        }
+        if respawn.countdown > 0 {
+            respawn.countdown -= 1;
+            continue;
+        }
+        if let Some(mut rng) = rngs.iter_mut().next() {
+            let spot = rng.below(num_players as u32) as usize;
+            spawn_ship(
+                &mut commands,
+                &mut rip,
+                &game_textures,
+                &classes,
+                respawn.handle,
+                NO_TEAM,
+                local_handles.handles.first() == Some(&respawn.handle),
+                spawn_location(spot, num_players),
+            );
+            respawn.countdown = rules.respawn_frames.unwrap_or(0);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::components::{Health, PlayerEntity, RoundEntity};
+
+use super::{team_color, GameMode, Team, TEAM_A, TEAM_B};
+
+/// Points needed to win, a team earns one per frame and per zone it holds.
+pub const SCORE_TO_WIN: u32 = 60 * 60;
+
+/// Control zones as (x, y, radius).
+const ZONES: [(f32, f32, f32); 3] = [(0., 0., 220.), (0., 650., 150.), (0., -650., 150.)];
+/// Capture progress gained per frame for each extra ship inside a zone.
+const CAPTURE_RATE: f32 = 1. / 180.;
+const ZONE_DOT_SIZE: f32 = 6.;
+const ZONE_DOT_SPACING: f32 = 24.;
+const NEUTRAL_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+const CONTESTED_COLOR: Color = Color::WHITE;
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct ControlZone {
+    pub radius: f32,
+    /// Owning team plus one, 0 while neutral.
+    pub owner: u8,
+    /// From -1 (held by team A) to 1 (held by team B).
+    pub progress: f32,
+    /// Both teams have the same, non zero, number of ships inside.
+    pub contested: bool,
+}
+
+impl ControlZone {
+    pub fn owned_by(&self, team: Team) -> bool {
+        self.owner == team + 1
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct HillScore {
+    pub points_a: u32,
+    pub points_b: u32,
+    /// Frames simulated since the round started.
+    pub frame: u32,
+    /// Frame at which a team reached the target score, 0 until then.
+    pub won_at: u32,
+}
+
+impl HillScore {
+    pub fn points(&self, team: Team) -> u32 {
+        if team == TEAM_B {
+            self.points_b
+        } else {
+            self.points_a
+        }
+    }
+
+    /// Team that reached the target score first, if any.
+    pub fn winner(&self) -> Option<Team> {
+        if self.points_a >= SCORE_TO_WIN && self.points_a >= self.points_b {
+            Some(TEAM_A)
+        } else if self.points_b >= SCORE_TO_WIN {
+            Some(TEAM_B)
+        } else {
+            None
+        }
+    }
+}
+
+/// Dot of the ring drawn around a control zone.
+#[derive(Component)]
+pub struct ZoneOutline;
+
+pub fn setup_koth(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    mode: Res<GameMode>,
+) {
+    if *mode != GameMode::KingOfTheHill {
+        return;
+    }
+
+    commands
+        .spawn()
+        .insert(HillScore::default())
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity);
+
+    for (x, y, radius) in ZONES {
+        let zone = ControlZone {
+            radius,
+            ..Default::default()
+        };
+        commands
+            .spawn_bundle(zone_bundle(
+                &zone,
+                Transform::from_translation(Vec3::new(x, y, 1.5)),
+            ))
+            .with_children(|parent| spawn_zone_outline(parent, &zone))
+            .insert(zone)
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+    }
+}
+
+pub fn zone_bundle(zone: &ControlZone, transform: Transform) -> SpriteBundle {
+    SpriteBundle {
+        transform,
+        sprite: Sprite {
+            color: NEUTRAL_COLOR,
+            custom_size: Some(Vec2::splat(zone.radius * std::f32::consts::SQRT_2)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Ring of dots around a zone, spawned as its children.
+pub fn spawn_zone_outline(parent: &mut ChildBuilder, zone: &ControlZone) {
+    let count = ((2. * std::f32::consts::PI * zone.radius) / ZONE_DOT_SPACING).ceil() as usize;
+    for i in 0..count {
+        let angle = i as f32 / count as f32 * 2. * std::f32::consts::PI;
+        parent
+            .spawn_bundle(SpriteBundle {
+                transform: Transform::from_translation(Vec3::new(
+                    angle.cos() * zone.radius,
+                    angle.sin() * zone.radius,
+                    0.,
+                )),
+                sprite: Sprite {
+                    color: NEUTRAL_COLOR,
+                    custom_size: Some(Vec2::splat(ZONE_DOT_SIZE)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(ZoneOutline);
+    }
+}
+
+/// Moves each zone towards the team with more ships inside and awards points
+/// to the owners.
+pub fn hill_system(
+    mut zones: Query<(&Rollback, &Transform, &mut ControlZone)>,
+    mut scores: Query<&mut HillScore>,
+    players: Query<(&Transform, &PlayerEntity, &Health)>,
+) {
+    let mut score = match scores.iter_mut().next() {
+        Some(score) => score,
+        None => return,
+    };
+    score.frame += 1;
+
+    let mut sorted_zones: Vec<_> = zones.iter_mut().collect();
+    sorted_zones.sort_by_key(|(rollback, _, _)| rollback.id());
+
+    for (_, transform, zone) in sorted_zones.iter_mut() {
+        let center = transform.translation.xy();
+        let mut inside = [0u32; 2];
+        for (player_tf, player, health) in players.iter() {
+            if health.hp > 0.
+                && (player.team as usize) < inside.len()
+                && player_tf.translation.xy().distance(center) < zone.radius
+            {
+                inside[player.team as usize] += 1;
+            }
+        }
+
+        zone.contested = inside[0] > 0 && inside[0] == inside[1];
+        let push = inside[1] as f32 - inside[0] as f32;
+        if push != 0. {
+            zone.progress = (zone.progress + push * CAPTURE_RATE).clamp(-1., 1.);
+            if zone.progress <= -1. {
+                zone.owner = 1;
+            } else if zone.progress >= 1. {
+                zone.owner = 2;
+            } else if zone.owner > 0 && (zone.progress < 0.) != zone.owned_by(TEAM_A) {
+                // pushed past neutral, the previous owner loses the zone
+                zone.owner = 0;
+            }
+        }
+
+        if score.won_at == 0 {
+            if zone.owned_by(TEAM_A) {
+                score.points_a += 1;
+            } else if zone.owned_by(TEAM_B) {
+                score.points_b += 1;
+            }
+        }
+    }
+
+    if score.won_at == 0 && score.winner().is_some() {
+        score.won_at = score.frame;
+    }
+}
+
+/// Colors zones after their owner, blinking while contested.
+pub fn zone_visual_system(
+    time: Res<Time>,
+    mut zones: Query<(&ControlZone, &Children, &mut Sprite), Without<ZoneOutline>>,
+    mut outlines: Query<&mut Sprite, With<ZoneOutline>>,
+) {
+    let blink = (time.seconds_since_startup() * 6.).sin() > 0.;
+    for (zone, children, mut sprite) in zones.iter_mut() {
+        let color = if zone.contested && blink {
+            CONTESTED_COLOR
+        } else if zone.owned_by(TEAM_A) {
+            team_color(TEAM_A)
+        } else if zone.owned_by(TEAM_B) {
+            team_color(TEAM_B)
+        } else {
+            NEUTRAL_COLOR
+        };
+
+        // the fill shows how far the capture has gone
+        let fill = if zone.progress < 0. {
+            team_color(TEAM_A)
+        } else {
+            team_color(TEAM_B)
+        };
+        sprite.color = Color::rgba(
+            fill.r(),
+            fill.g(),
+            fill.b(),
+            0.1 + zone.progress.abs() * 0.3,
+        );
+
+        for child in children.iter() {
+            if let Ok(mut outline) = outlines.get_mut(*child) {
+                outline.color = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+pub mod ctf;
+pub mod ffa;
+pub mod koth;
+pub mod survival;
+
+use bevy::prelude::Color;
+
+use crate::{FPS, NUM_ALLIES, NUM_PLAYERS};
+
+/// Side a player fights for.
+pub type Team = u8;
+pub const TEAM_A: Team = 0;
+pub const TEAM_B: Team = 1;
+/// Players without a team fight everybody else.
+pub const NO_TEAM: Team = u8::MAX;
+
+/// Round length of the modes that have no time limit of their own, in seconds.
+const ROUND_SECS: u32 = 180;
+/// Objective modes take longer to play out, in seconds.
+const OBJECTIVE_ROUND_SECS: u32 = 300;
+
+const TEAM_COLORS: [Color; 2] = [Color::rgb(0.3, 0.6, 1.0), Color::rgb(1.0, 0.55, 0.1)];
+const PLAYER_COLORS: [Color; 8] = [
+    Color::rgb(0.3, 0.6, 1.0),
+    Color::rgb(1.0, 0.55, 0.1),
+    Color::rgb(0.4, 1.0, 0.4),
+    Color::rgb(1.0, 0.35, 0.35),
+    Color::rgb(0.8, 0.45, 1.0),
+    Color::rgb(1.0, 0.95, 0.3),
+    Color::rgb(0.3, 1.0, 0.95),
+    Color::rgb(1.0, 0.5, 0.8),
+];
+
+pub fn team_color(team: Team) -> Color {
+    TEAM_COLORS[team as usize % TEAM_COLORS.len()]
+}
+
+/// Tint telling players apart when there are no teams.
+pub fn player_color(handle: usize) -> Color {
+    PLAYER_COLORS[handle % PLAYER_COLORS.len()]
+}
+
+pub fn team_name(team: Team) -> &'static str {
+    match team {
+        TEAM_A => "Blue",
+        TEAM_B => "Orange",
+        _ => "-",
+    }
+}
+
+/// The other team, players without a team stay without one.
+pub fn opposing_team(team: Team) -> Team {
+    match team {
+        TEAM_A => TEAM_B,
+        TEAM_B => TEAM_A,
+        _ => team,
+    }
+}
+
+/// Whether players of these teams fight each other.
+pub fn are_enemies(team: Team, other: Team) -> bool {
+    team == NO_TEAM || team != other
+}
+
+/// Rules that vary from one mode to the other.
+pub struct MatchRules {
+    /// Lasers and rams hurt teammates too.
+    pub friendly_fire: bool,
+    /// Kills needed to win the match.
+    pub frag_limit: Option<u32>,
+    /// Length of a round in frames, the side ahead wins when it runs out.
+    pub time_limit: Option<u32>,
+    /// Frames a destroyed ship waits before coming back, `None` keeps it out.
+    pub respawn_frames: Option<u32>,
+}
+
+impl MatchRules {
+    /// Whether a player of `team` can damage one of `other`.
+    pub fn can_hurt(&self, team: Team, other: Team) -> bool {
+        self.friendly_fire || are_enemies(team, other)
+    }
+}
+
+/// Options of the match on top of its mode, picked in the main menu. Online
+/// peers only meet others with the same options.
+pub struct MatchOptions {
+    /// Ships ramming each other take damage from the crash, otherwise they
+    /// only bounce off.
+    pub ram_damage: bool,
+    /// Lasers and rams hurt teammates too, in the modes with two teams.
+    pub friendly_fire: bool,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            ram_damage: true,
+            friendly_fire: false,
+        }
+    }
+}
+
+/// Rules of the match, picked in the menus before the session starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Team against team, the original mode.
+    Versus,
+    /// Every player on the same side against waves of invaders.
+    Survival,
+    /// Bring the enemy flag back to your base.
+    CaptureTheFlag,
+    /// Hold the control zones to score.
+    KingOfTheHill,
+    /// Everybody for themselves, until the frag limit or the clock runs out.
+    FreeForAll,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Versus
+    }
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Versus => "Versus",
+            GameMode::Survival => "Survival",
+            GameMode::CaptureTheFlag => "CTF",
+            GameMode::KingOfTheHill => "Hill",
+            GameMode::FreeForAll => "FFA",
+        }
+    }
+
+    /// Short tag used to keep players of different modes in separate rooms.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            GameMode::Versus => "vs",
+            GameMode::Survival => "surv",
+            GameMode::CaptureTheFlag => "ctf",
+            GameMode::KingOfTheHill => "koth",
+            GameMode::FreeForAll => "ffa",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            GameMode::Versus => GameMode::Survival,
+            GameMode::Survival => GameMode::CaptureTheFlag,
+            GameMode::CaptureTheFlag => GameMode::KingOfTheHill,
+            GameMode::KingOfTheHill => GameMode::FreeForAll,
+            GameMode::FreeForAll => GameMode::Versus,
+        }
+    }
+
+    /// Whether players are split in two opposing teams.
+    pub fn has_teams(&self) -> bool {
+        matches!(
+            self,
+            GameMode::Versus | GameMode::CaptureTheFlag | GameMode::KingOfTheHill
+        )
+    }
+
+    /// Number of players in a match, bots included.
+    pub fn num_players(&self) -> usize {
+        match self {
+            GameMode::FreeForAll => ffa::FFA_PLAYERS,
+            _ => NUM_PLAYERS,
+        }
+    }
+
+    /// Team of the given player.
+    pub fn team(&self, handle: usize) -> Team {
+        match self {
+            GameMode::Versus | GameMode::CaptureTheFlag | GameMode::KingOfTheHill => {
+                if handle >= NUM_ALLIES {
+                    TEAM_B
+                } else {
+                    TEAM_A
+                }
+            }
+            GameMode::Survival => TEAM_A,
+            GameMode::FreeForAll => NO_TEAM,
+        }
+    }
+
+    pub fn rules(&self, options: &MatchOptions) -> MatchRules {
+        // teammates only hurt each other when asked to, the cooperative
+        // survival stays forgiving
+        let friendly_fire = options.friendly_fire && self.has_teams();
+        match self {
+            GameMode::FreeForAll => MatchRules {
+                friendly_fire,
+                frag_limit: Some(ffa::FRAG_LIMIT),
+                time_limit: Some(ROUND_SECS * FPS as u32),
+                respawn_frames: Some(ffa::RESPAWN_SECS * FPS as u32),
+            },
+            GameMode::Versus => MatchRules {
+                friendly_fire,
+                frag_limit: None,
+                time_limit: Some(ROUND_SECS * FPS as u32),
+                respawn_frames: None,
+            },
+            GameMode::CaptureTheFlag => MatchRules {
+                friendly_fire,
+                frag_limit: None,
+                time_limit: Some(OBJECTIVE_ROUND_SECS * FPS as u32),
+                respawn_frames: None,
+            },
+            GameMode::KingOfTheHill => MatchRules {
+                friendly_fire,
+                frag_limit: None,
+                time_limit: Some(OBJECTIVE_ROUND_SECS * FPS as u32),
+                respawn_frames: None,
+            },
+            GameMode::Survival => MatchRules {
+                friendly_fire,
+                frag_limit: None,
+                time_limit: None,
+                respawn_frames: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(friendly_fire: bool) -> MatchOptions {
+        MatchOptions {
+            friendly_fire,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn teammates_are_safe_by_default() {
+        let rules = GameMode::Versus.rules(&MatchOptions::default());
+        assert!(!rules.can_hurt(TEAM_A, TEAM_A));
+        assert!(rules.can_hurt(TEAM_A, TEAM_B));
+        assert!(rules.can_hurt(TEAM_B, TEAM_A));
+    }
+
+    #[test]
+    fn friendly_fire_lets_teammates_hurt_each_other() {
+        let rules = GameMode::CaptureTheFlag.rules(&options(true));
+        assert!(rules.can_hurt(TEAM_A, TEAM_A));
+        assert!(rules.can_hurt(TEAM_A, TEAM_B));
+    }
+
+    #[test]
+    fn players_without_a_team_hurt_everybody() {
+        let rules = GameMode::FreeForAll.rules(&MatchOptions::default());
+        assert!(rules.can_hurt(NO_TEAM, NO_TEAM));
+    }
+
+    #[test]
+    fn friendly_fire_only_applies_to_team_modes() {
+        assert!(!GameMode::Survival.rules(&options(true)).friendly_fire);
+        assert!(!GameMode::FreeForAll.rules(&options(true)).friendly_fire);
+        assert!(GameMode::KingOfTheHill.rules(&options(true)).friendly_fire);
+    }
+}
@@ -0,0 +1,512 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::{
+    collision::{hitboxes_intersect, Broadphase, Hitbox},
+    components::{
+        AngularVelocity, ExplosionToSpawn, Health, Laser, Movable, PlayerEntity, PlayerScore,
+        RoundEntity, Velocity, KILLED_BY_INVADER,
+    },
+    game::ARENA_SIZE,
+    pickups::PowerUps,
+    rng::RollbackRng,
+    weapons::laser_bundle,
+    ImageAssets,
+};
+
+use super::{GameMode, TEAM_B};
+
+pub const INVADER_DRONE: u8 = 0;
+pub const INVADER_GUNNER: u8 = 1;
+pub const INVADER_BRUTE: u8 = 2;
+
+/// Shooter handle of invader lasers, matches no player.
+pub const INVADER_HANDLE: usize = usize::MAX;
+
+/// Frames before the first wave, to let players get their bearings.
+const FIRST_WAVE_DELAY: u32 = 180;
+/// Frames between clearing a wave and the next one.
+const WAVE_DELAY: u32 = 240;
+/// Extra health per wave, as a fraction of the base health.
+const WAVE_HEALTH_GROWTH: f32 = 0.1;
+/// How much invader velocity follows the wanted direction each frame.
+const INVADER_STEERING: f32 = 0.05;
+const INVADER_LASER_SIZE: (f32, f32) = (17., 55.);
+/// Hitbox radius in sprite pixels, scaled along with the sprite.
+const INVADER_RADIUS: f32 = 40.;
+
+pub struct InvaderStats {
+    pub name: &'static str,
+    pub health: f32,
+    pub speed: f32,
+    /// Distance kept from the targeted ship, 0 charges straight at it.
+    pub range: f32,
+    /// Frames between two volleys, 0 never fires.
+    pub fire_interval: u32,
+    pub projectiles: u32,
+    /// Angle between two projectiles of the same volley, in radians.
+    pub spread: f32,
+    pub projectile_speed: f32,
+    pub damage: f32,
+    /// Damage dealt by crashing into a ship, 0 does not ram.
+    pub contact_damage: f32,
+    pub points: u32,
+    pub scale: f32,
+    pub color: Color,
+}
+
+pub static INVADERS: [InvaderStats; 3] = [
+    InvaderStats {
+        name: "Drone",
+        health: 30.,
+        speed: 0.45,
+        range: 0.,
+        fire_interval: 0,
+        projectiles: 0,
+        spread: 0.,
+        projectile_speed: 0.,
+        damage: 0.,
+        contact_damage: 35.,
+        points: 100,
+        scale: 0.5,
+        color: Color::rgb(1.0, 0.5, 0.5),
+    },
+    InvaderStats {
+        name: "Gunner",
+        health: 60.,
+        speed: 0.3,
+        range: 450.,
+        fire_interval: 90,
+        projectiles: 1,
+        spread: 0.,
+        projectile_speed: 0.8,
+        damage: 15.,
+        contact_damage: 0.,
+        points: 250,
+        scale: 0.8,
+        color: Color::WHITE,
+    },
+    InvaderStats {
+        name: "Brute",
+        health: 200.,
+        speed: 0.15,
+        range: 300.,
+        fire_interval: 150,
+        projectiles: 3,
+        spread: 0.25,
+        projectile_speed: 0.6,
+        damage: 20.,
+        contact_damage: 0.,
+        points: 600,
+        scale: 1.4,
+        color: Color::rgb(0.7, 0.5, 1.0),
+    },
+];
+
+/// Progress of a survival match.
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Waves {
+    /// Current wave, 0 before the first one.
+    pub wave: u32,
+    /// Frames left before the next wave comes in.
+    pub countdown: u32,
+    pub score: u32,
+    /// Frames simulated since the round started.
+    pub frame: u32,
+    /// Frame at which the last ship went down, 0 while someone is alive.
+    pub overrun_at: u32,
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Invader {
+    pub kind: u8,
+    /// Frames left before the next volley.
+    pub cooldown: u32,
+}
+
+impl Invader {
+    pub fn stats(&self) -> &'static InvaderStats {
+        &INVADERS[self.kind as usize % INVADERS.len()]
+    }
+}
+
+pub fn setup_survival(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    mode: Res<GameMode>,
+) {
+    if *mode != GameMode::Survival {
+        return;
+    }
+    commands
+        .spawn()
+        .insert(Waves {
+            countdown: FIRST_WAVE_DELAY,
+            ..Default::default()
+        })
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity);
+}
+
+/// Sends the next wave once the previous one is cleared, and notes when the
+/// players have all been destroyed.
+pub fn wave_system(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    game_textures: Res<ImageAssets>,
+    mut waves: Query<&mut Waves>,
+    mut rngs: Query<&mut RollbackRng>,
+    invaders: Query<&Invader>,
+    players: Query<&PlayerEntity>,
+) {
+    let mut rng = match rngs.iter_mut().next() {
+        Some(rng) => rng,
+        None => return,
+    };
+
+    for mut waves in waves.iter_mut() {
+        waves.frame += 1;
+        if waves.overrun_at == 0 && players.iter().next().is_none() {
+            waves.overrun_at = waves.frame;
+        }
+        if waves.overrun_at > 0 || invaders.iter().next().is_some() {
+            continue;
+        }
+        if waves.countdown > 0 {
+            waves.countdown -= 1;
+            continue;
+        }
+
+        waves.wave += 1;
+        waves.countdown = WAVE_DELAY;
+        let wave = waves.wave;
+        for _ in 0..2 + 2 * wave {
+            let kind = if wave >= 4 && rng.below(6) == 0 {
+                INVADER_BRUTE
+            } else if wave >= 2 && rng.below(3) == 0 {
+                INVADER_GUNNER
+            } else {
+                INVADER_DRONE
+            };
+
+            // come in from a random point along one of the borders
+            let half = ARENA_SIZE / 2.;
+            let along = rng.range(-half, half);
+            let position = match rng.below(4) {
+                0 => Vec2::new(along, half),
+                1 => Vec2::new(along, -half),
+                2 => Vec2::new(half, along),
+                _ => Vec2::new(-half, along),
+            };
+            spawn_invader(
+                &mut commands,
+                &mut rip,
+                &game_textures,
+                kind,
+                wave,
+                position,
+            );
+        }
+    }
+}
+
+fn spawn_invader(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    game_textures: &ImageAssets,
+    kind: u8,
+    wave: u32,
+    position: Vec2,
+) {
+    let invader = Invader {
+        kind,
+        cooldown: INVADERS[kind as usize].fire_interval,
+    };
+    let stats = invader.stats();
+    let health = stats.health * (1. + WAVE_HEALTH_GROWTH * (wave - 1) as f32);
+
+    let transform = Transform {
+        translation: Vec3::new(position.x, position.y, 3.),
+        rotation: Quat::from_rotation_z((-position.y).atan2(-position.x)),
+        scale: Vec3::new(stats.scale, stats.scale, 1.),
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(invader_bundle(game_textures, &invader, transform))
+        .insert(Velocity::default())
+        .insert(Movable {
+            auto_despawn: false,
+            steerable: false,
+        })
+        .insert(AngularVelocity { angle: 0. })
+        .insert(Health {
+            hp: health,
+            max: health,
+        })
+        .insert(Hitbox::Circle {
+            radius: INVADER_RADIUS,
+        })
+        .insert(invader)
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity);
+}
+
+pub fn invader_bundle(
+    game_textures: &ImageAssets,
+    invader: &Invader,
+    transform: Transform,
+) -> SpriteBundle {
+    SpriteBundle {
+        texture: game_textures.ennemy.clone(),
+        transform,
+        sprite: Sprite {
+            color: invader.stats().color,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Moves invaders towards the closest ship and fires their guns.
+pub fn invader_ai_system(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    game_textures: Res<ImageAssets>,
+    mut invaders: Query<
+        (
+            Entity,
+            &Rollback,
+            &mut Transform,
+            &mut Velocity,
+            &mut Invader,
+        ),
+        Without<PlayerEntity>,
+    >,
+    players: Query<(&Transform, &PlayerEntity), Without<Invader>>,
+) {
+    // fire in rollback id order so projectiles get the same ids on every peer
+    let mut order: Vec<(u32, Entity)> = invaders
+        .iter()
+        .map(|(entity, rollback, _, _, _)| (rollback.id(), entity))
+        .collect();
+    order.sort_unstable();
+
+    for (_, entity) in order {
+        let (_, _, mut transform, mut velocity, mut invader) = invaders.get_mut(entity).unwrap();
+        let stats = invader.stats();
+        let position = transform.translation.xy();
+
+        // closest ship, ties broken by handle
+        let target = players
+            .iter()
+            .map(|(player_tf, player)| {
+                (
+                    player_tf.translation.xy().distance_squared(position),
+                    player.handle,
+                    player_tf.translation.xy(),
+                )
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let (offset, distance) = match target {
+            Some((_, _, target)) => (target - position, target.distance(position)),
+            None => (-position, 0.),
+        };
+        let facing = offset.normalize_or_zero();
+
+        // hold at range by backing off or circling, drones just charge in
+        let direction = if target.is_none() || stats.range <= 0. {
+            facing
+        } else if distance < stats.range * 0.8 {
+            -facing
+        } else if distance < stats.range {
+            facing.perp()
+        } else {
+            facing
+        };
+        let wanted = direction * stats.speed;
+        velocity.x += (wanted.x - velocity.x) * INVADER_STEERING;
+        velocity.y += (wanted.y - velocity.y) * INVADER_STEERING;
+        if facing != Vec2::ZERO {
+            transform.rotation = Quat::from_rotation_z(facing.y.atan2(facing.x));
+        }
+
+        if stats.fire_interval == 0 {
+            continue;
+        }
+        if invader.cooldown > 0 {
+            invader.cooldown -= 1;
+            continue;
+        }
+        if target.is_none() || distance > stats.range * 1.5 {
+            continue;
+        }
+        invader.cooldown = stats.fire_interval;
+
+        let laser_size = Vec2::new(INVADER_LASER_SIZE.0, INVADER_LASER_SIZE.1);
+        for i in 0..stats.projectiles {
+            let angle = (i as f32 - (stats.projectiles - 1) as f32 / 2.) * stats.spread;
+            let rotation = transform.rotation * Quat::from_rotation_z(angle);
+            let dir = rotation * Vec3::X * stats.projectile_speed;
+            let laser = Laser {
+                player_handle: INVADER_HANDLE,
+                player_team: TEAM_B,
+                size: laser_size,
+                damage: stats.damage,
+            };
+            let laser_tf = Transform {
+                translation: Vec3::new(position.x, position.y, 2.),
+                rotation: rotation.mul_quat(Quat::from_rotation_z((-90.0_f32).to_radians())),
+                scale: Vec3::new(0.5, 0.5, 1.),
+                ..Default::default()
+            };
+            commands
+                .spawn_bundle(laser_bundle(&game_textures, &laser, laser_tf))
+                .insert(Movable {
+                    auto_despawn: true,
+                    steerable: false,
+                })
+                .insert(Velocity {
+                    x: dir.x + velocity.x,
+                    y: dir.y + velocity.y,
+                })
+                .insert(AngularVelocity { angle: 0. })
+                .insert(Rollback::new(rip.next_id()))
+                .insert(laser)
+                .insert(Hitbox::Obb {
+                    half_extents: laser_size / 2.,
+                })
+                .insert(RoundEntity);
+        }
+    }
+}
+
+/// Drones blowing up on contact with a ship.
+pub fn invader_contact_system(
+    mut commands: Commands,
+    broadphase: Res<Broadphase>,
+    invaders: Query<(Entity, &Rollback, &Transform, &Hitbox, &Invader), Without<PlayerEntity>>,
+    mut players: Query<
+        (
+            Entity,
+            &Transform,
+            &Hitbox,
+            &PlayerEntity,
+            &mut Health,
+            &mut PowerUps,
+        ),
+        Without<Invader>,
+    >,
+    mut scores: Query<&mut PlayerScore>,
+) {
+    let mut sorted_invaders: Vec<_> = invaders
+        .iter()
+        .filter(|(_, _, _, _, invader)| invader.stats().contact_damage > 0.)
+        .collect();
+    sorted_invaders.sort_by_key(|(_, rollback, _, _, _)| rollback.id());
+
+    let mut candidates = Vec::new();
+    for (invader_entity, _, invader_tf, invader_hitbox, invader) in sorted_invaders {
+        broadphase.query(
+            invader_tf.translation.xy(),
+            invader_hitbox.bounding_radius(invader_tf),
+            &mut candidates,
+        );
+        for (_, candidate) in candidates.iter() {
+            let (player_entity, player_tf, player_hitbox, player, mut health, mut powerups) =
+                match players.get_mut(*candidate) {
+                    Ok(player) => player,
+                    Err(_) => continue,
+                };
+            if health.hp <= 0.
+                || !hitboxes_intersect(invader_hitbox, invader_tf, player_hitbox, player_tf)
+            {
+                continue;
+            }
+
+            let remaining = powerups.absorb(invader.stats().contact_damage);
+            health.hp -= remaining.min(health.hp);
+            if health.hp <= 0. {
+                for mut score in scores.iter_mut() {
+                    if score.handle == player.handle {
+                        score.deaths += 1;
+                        score.killed_by = KILLED_BY_INVADER;
+                    }
+                }
+                commands.spawn().insert(ExplosionToSpawn {
+                    translation: player_tf.translation,
+                });
+                commands.entity(player_entity).despawn_recursive();
+            }
+            commands.spawn().insert(ExplosionToSpawn {
+                translation: invader_tf.translation,
+            });
+            commands.entity(invader_entity).despawn_recursive();
+            break;
+        }
+    }
+}
+
+/// Player lasers hitting invaders.
+pub fn invader_hit_system(
+    mut commands: Commands,
+    broadphase: Res<Broadphase>,
+    lasers: Query<(Entity, &Rollback, &Transform, &Laser, &Hitbox), Without<Invader>>,
+    mut invaders: Query<(Entity, &Transform, &Hitbox, &Invader, &mut Health), Without<Laser>>,
+    mut scores: Query<&mut PlayerScore>,
+    mut waves: Query<&mut Waves>,
+) {
+    let mut sorted_lasers: Vec<_> = lasers
+        .iter()
+        .filter(|(_, _, _, laser, _)| laser.player_team != TEAM_B)
+        .collect();
+    sorted_lasers.sort_by_key(|(_, rollback, _, _, _)| rollback.id());
+
+    let mut candidates = Vec::new();
+    for (laser_entity, _, laser_tf, laser, laser_hitbox) in sorted_lasers {
+        broadphase.query(
+            laser_tf.translation.xy(),
+            laser_hitbox.bounding_radius(laser_tf),
+            &mut candidates,
+        );
+        for (_, candidate) in candidates.iter() {
+            let (invader_entity, invader_tf, invader_hitbox, invader, mut health) =
+                match invaders.get_mut(*candidate) {
+                    Ok(invader) => invader,
+                    Err(_) => continue,
+                };
+            if health.hp <= 0.
+                || !hitboxes_intersect(laser_hitbox, laser_tf, invader_hitbox, invader_tf)
+            {
+                continue;
+            }
+
+            let damage = laser.damage.min(health.hp);
+            health.hp -= damage;
+            let killed = health.hp <= 0.;
+            for mut score in scores.iter_mut() {
+                if score.handle == laser.player_handle {
+                    score.shots_hit += 1;
+                    score.damage_dealt += damage;
+                    if killed {
+                        score.kills += 1;
+                    }
+                }
+            }
+
+            commands.spawn().insert(ExplosionToSpawn {
+                translation: laser_tf.translation,
+            });
+            if killed {
+                for mut waves in waves.iter_mut() {
+                    waves.score += invader.stats().points * waves.wave.max(1);
+                }
+                commands.entity(invader_entity).despawn_recursive();
+            }
+            commands.entity(laser_entity).despawn_recursive();
+            break;
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::{
+    collision::{hitboxes_intersect, Broadphase, Hitbox},
+    components::{Health, PlayerEntity, RoundEntity},
+    game::ARENA_SIZE,
+    rng::RollbackRng,
+};
+
+pub const PICKUP_REPAIR: u8 = 0;
+pub const PICKUP_SHIELD: u8 = 1;
+pub const PICKUP_WEAPON: u8 = 2;
+pub const PICKUP_SPEED: u8 = 3;
+const PICKUP_KINDS: u8 = 4;
+
+const PICKUP_INTERVAL: u32 = 600;
+const PICKUP_MAX: usize = 4;
+const PICKUP_RADIUS: f32 = 18.;
+const PICKUP_COLORS: [Color; 4] = [Color::GREEN, Color::CYAN, Color::ORANGE, Color::YELLOW];
+
+/// Fixed spots where pickups can appear, the rest of the time they land anywhere in the arena.
+const PICKUP_SPOTS: [(f32, f32); 5] = [
+    (0., 0.),
+    (-600., -600.),
+    (600., -600.),
+    (-600., 600.),
+    (600., 600.),
+];
+
+const REPAIR_AMOUNT: f32 = 50.;
+pub const SHIELD_AMOUNT: f32 = 60.;
+pub const POWERUP_FRAMES: u32 = 600;
+pub const SPEED_BOOST: f32 = 1.6;
+pub const WEAPON_BOOST_DAMAGE: f32 = 1.5;
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Pickup {
+    pub kind: u8,
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct PickupSpawner {
+    /// Frames left before the next pickup appears.
+    pub countdown: u32,
+}
+
+/// Timed effects granted by pickups.
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct PowerUps {
+    /// Damage absorbed before the hull is hit.
+    pub shield: f32,
+    pub shield_frames: u32,
+    pub weapon_frames: u32,
+    pub speed_frames: u32,
+}
+
+impl PowerUps {
+    /// Lets the shield absorb what it can and returns the damage left for the hull.
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let absorbed = damage.min(self.shield);
+        self.shield -= absorbed;
+        damage - absorbed
+    }
+
+    pub fn thrust_factor(&self) -> f32 {
+        if self.speed_frames > 0 {
+            SPEED_BOOST
+        } else {
+            1.
+        }
+    }
+
+    pub fn damage_factor(&self) -> f32 {
+        if self.weapon_frames > 0 {
+            WEAPON_BOOST_DAMAGE
+        } else {
+            1.
+        }
+    }
+}
+
+pub fn setup_pickups(mut commands: Commands, mut rip: ResMut<RollbackIdProvider>) {
+    commands
+        .spawn()
+        .insert(PickupSpawner {
+            countdown: PICKUP_INTERVAL,
+        })
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity);
+}
+
+pub fn pickup_spawn_system(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    mut spawners: Query<&mut PickupSpawner>,
+    mut rngs: Query<&mut RollbackRng>,
+    pickups: Query<&Pickup>,
+) {
+    let mut rng = match rngs.iter_mut().next() {
+        Some(rng) => rng,
+        None => return,
+    };
+
+    for mut spawner in spawners.iter_mut() {
+        if spawner.countdown > 0 {
+            spawner.countdown -= 1;
+            continue;
+        }
+        spawner.countdown = PICKUP_INTERVAL;
+        if pickups.iter().count() >= PICKUP_MAX {
+            continue;
+        }
+
+        let kind = rng.below(PICKUP_KINDS as u32) as u8;
+        let spot = rng.below(PICKUP_SPOTS.len() as u32 * 2) as usize;
+        let position = if spot < PICKUP_SPOTS.len() {
+            Vec2::new(PICKUP_SPOTS[spot].0, PICKUP_SPOTS[spot].1)
+        } else {
+            let half = ARENA_SIZE / 2. - PICKUP_RADIUS;
+            Vec2::new(rng.range(-half, half), rng.range(-half, half))
+        };
+
+        let pickup = Pickup { kind };
+        let transform = Transform {
+            translation: Vec3::new(position.x, position.y, 2.5),
+            rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            ..Default::default()
+        };
+        commands
+            .spawn_bundle(pickup_bundle(&pickup, transform))
+            .insert(pickup)
+            .insert(Hitbox::Circle {
+                radius: PICKUP_RADIUS,
+            })
+            .insert(Rollback::new(rip.next_id()))
+            .insert(RoundEntity);
+    }
+}
+
+pub fn pickup_bundle(pickup: &Pickup, transform: Transform) -> SpriteBundle {
+    SpriteBundle {
+        transform,
+        sprite: Sprite {
+            color: PICKUP_COLORS[pickup.kind as usize],
+            custom_size: Some(Vec2::splat(PICKUP_RADIUS * 1.4)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn pickup_collect_system(
+    mut commands: Commands,
+    broadphase: Res<Broadphase>,
+    pickups: Query<(Entity, &Rollback, &Transform, &Hitbox, &Pickup), Without<PlayerEntity>>,
+    mut players: Query<
+        (&Transform, &Hitbox, &mut Health, &mut PowerUps),
+        (With<PlayerEntity>, Without<Pickup>),
+    >,
+) {
+    let mut sorted_pickups: Vec<_> = pickups.iter().collect();
+    sorted_pickups.sort_by_key(|(_, rollback, _, _, _)| rollback.id());
+
+    let mut candidates = Vec::new();
+    for (pickup_entity, _, pickup_tf, pickup_hitbox, pickup) in sorted_pickups {
+        broadphase.query(
+            pickup_tf.translation.xy(),
+            pickup_hitbox.bounding_radius(pickup_tf),
+            &mut candidates,
+        );
+        for (_, candidate) in candidates.iter() {
+            let (player_tf, player_hitbox, mut health, mut powerups) =
+                match players.get_mut(*candidate) {
+                    Ok(player) => player,
+                    Err(_) => continue,
+                };
+            if health.hp <= 0.
+                || !hitboxes_intersect(pickup_hitbox, pickup_tf, player_hitbox, player_tf)
+            {
+                continue;
+            }
+
+            match pickup.kind {
+                PICKUP_REPAIR => health.hp = (health.hp + REPAIR_AMOUNT).min(health.max),
+                PICKUP_SHIELD => {
+                    powerups.shield = SHIELD_AMOUNT;
+                    powerups.shield_frames = POWERUP_FRAMES;
+                }
+                PICKUP_WEAPON => powerups.weapon_frames = POWERUP_FRAMES,
+                _ => powerups.speed_frames = POWERUP_FRAMES,
+            }
+            commands.entity(pickup_entity).despawn_recursive();
+            break;
+        }
+    }
+}
+
+pub fn powerup_tick_system(mut query: Query<&mut PowerUps>) {
+    for mut powerups in query.iter_mut() {
+        if powerups.shield_frames > 0 {
+            powerups.shield_frames -= 1;
+            if powerups.shield_frames == 0 {
+                powerups.shield = 0.;
+            }
+        }
+        if powerups.weapon_frames > 0 {
+            powerups.weapon_frames -= 1;
+        }
+        if powerups.speed_frames > 0 {
+            powerups.speed_frames -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shield_absorbs_damage_before_the_hull() {
+        let mut powerups = PowerUps {
+            shield: 30.,
+            ..Default::default()
+        };
+        assert_eq!(powerups.absorb(20.), 0.);
+        assert_eq!(powerups.shield, 10.);
+        assert_eq!(powerups.absorb(25.), 15.);
+        assert_eq!(powerups.shield, 0.);
+        assert_eq!(powerups.absorb(5.), 5.);
+    }
+
+    #[test]
+    fn boosts_only_apply_while_they_last() {
+        let mut powerups = PowerUps::default();
+        assert_eq!(powerups.thrust_factor(), 1.);
+        assert_eq!(powerups.damage_factor(), 1.);
+
+        powerups.speed_frames = 1;
+        powerups.weapon_frames = 1;
+        assert_eq!(powerups.thrust_factor(), SPEED_BOOST);
+        assert_eq!(powerups.damage_factor(), WEAPON_BOOST_DAMAGE);
+    }
+}
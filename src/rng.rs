@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::components::{RoundEntity, SessionEntity};
+
+/// Seed shared by every peer of the session, set up before the round starts.
+pub struct SessionSeed(pub u64);
+
+impl SessionSeed {
+    /// Derives the seed from the ids of all peers, so everyone agrees on it
+    /// without exchanging an extra message.
+    pub fn from_peer_ids<'a>(ids: impl Iterator<Item = &'a str>) -> Self {
+        let mut ids: Vec<&str> = ids.collect();
+        ids.sort_unstable();
+
+        // FNV-1a, stable across platforms and compiler versions unlike `DefaultHasher`
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for id in ids {
+            for byte in id.bytes().chain(std::iter::once(0)) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        Self(hash)
+    }
+}
+
+/// Random number generator of the simulation.
+///
+/// It lives on a rollback entity so its state is saved and restored with the
+/// rest of the game, and is the only source of randomness allowed in rollback
+/// systems. Systems drawing from it must be explicitly ordered, the sequence of
+/// numbers each one gets depends on it.
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct RollbackRng {
+    state: u64,
+}
+
+impl RollbackRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// splitmix64, small and fast, good enough for gameplay.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform integer in `0..n`.
+    pub fn below(&mut self, n: u32) -> u32 {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as u32
+    }
+
+    /// Uniform float in `0..1`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform float in `min..max`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+pub fn setup_rng(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    seed: Res<SessionSeed>,
+) {
+    commands
+        .spawn()
+        .insert(RollbackRng::new(seed.0))
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_ignores_peer_order() {
+        let seed = SessionSeed::from_peer_ids(["bob", "alice"].into_iter()).0;
+        assert_eq!(
+            seed,
+            SessionSeed::from_peer_ids(["alice", "bob"].into_iter()).0
+        );
+        // pinned so a change of hash between builds can't go unnoticed
+        assert_eq!(seed, 0x77fc_56eb_416e_39fc);
+    }
+
+    #[test]
+    fn seed_tells_peer_ids_apart() {
+        let seed = SessionSeed::from_peer_ids(["ab", "c"].into_iter()).0;
+        assert_ne!(seed, SessionSeed::from_peer_ids(["a", "bc"].into_iter()).0);
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut rng = RollbackRng::new(42);
+        let mut other = RollbackRng::new(42);
+        for _ in 0..100 {
+            assert_eq!(rng.next_u64(), other.next_u64());
+        }
+    }
+
+    #[test]
+    fn sequence_matches_splitmix64() {
+        let mut rng = RollbackRng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+    }
+
+    #[test]
+    fn draws_stay_in_range() {
+        let mut rng = RollbackRng::new(7);
+        for _ in 0..1000 {
+            assert!(rng.below(5) < 5);
+            let f = rng.next_f32();
+            assert!((0. ..1.).contains(&f));
+            let r = rng.range(-3., 2.);
+            assert!((-3. ..=2.).contains(&r));
+        }
+    }
+}
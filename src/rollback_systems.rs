@@ -1,15 +1,30 @@
 use bevy::{math::Vec3Swizzles, prelude::*};
 
+use std::collections::HashMap;
+
 use crate::{
+    collision::{hitboxes_intersect, Broadphase, Hitbox},
     components::{
-        AngularVelocity, ExplosionToSpawn, Laser, Movable, PlayerEntity, ThrustEngine, Velocity,
+        AngularVelocity, Energy, ExplosionToSpawn, Health, Laser, Movable, PlayerEntity,
+        PlayerScore, ThrustEngine, Velocity,
     },
     components::{Explosion, ExplosionTimer, FrameCount, Input, RoundEntity},
-    game::{ARENA_SIZE, INPUT_LEFT, INPUT_RIGHT, INPUT_SPACE, INPUT_UP, LASER_SPEED},
+    game::{
+        ARENA_SIZE, ENERGY_REGEN, ENERGY_REGEN_DELAY, INPUT_LEFT, INPUT_RIGHT, INPUT_SPACE,
+        INPUT_SWITCH, INPUT_UP, LASER_SPEED, RAM_DAMAGE_FACTOR, RAM_MIN_SPEED, SHIP_RESTITUTION,
+        THRUST_ENERGY_COST,
+    },
     menu::connect::LocalHandles,
+    modes::{
+        are_enemies,
+        ctf::{Flag, CARRIER_THRUST_FACTOR},
+        GameMode, MatchOptions, Team,
+    },
+    pickups::PowerUps,
+    ships::Steering,
+    weapons::{fire_weapon, Weapon},
     ImageAssets, BASE_SPEED, EXPLOSION_LEN, LASER_SCALE, TIME_STEP,
 };
-use bevy::sprite::collide_aabb::collide;
 use bevy_ggrs::{Rollback, RollbackIdProvider};
 use ggrs::InputStatus;
 
@@ -24,14 +39,26 @@ pub fn apply_inputs(
             &mut Transform,
             &mut ThrustEngine,
             &mut AngularVelocity,
+            &mut Energy,
+            &Steering,
+            &PowerUps,
             &PlayerEntity,
         ),
         With<PlayerEntity>,
     >,
+    flags: Query<&Flag>,
     inputs: Res<Vec<(Input, InputStatus)>>,
 ) {
-    for (mut velocity, mut transform, mut thrust_engine, mut angular_velocity, player) in
-        query.iter_mut()
+    for (
+        mut velocity,
+        mut transform,
+        mut thrust_engine,
+        mut angular_velocity,
+        mut energy,
+        steering,
+        powerups,
+        player,
+    ) in query.iter_mut()
     {
         let input = match inputs[player.handle].1 {
             InputStatus::Confirmed => inputs[player.handle].0.inp,
@@ -40,17 +67,23 @@ pub fn apply_inputs(
         };
 
         angular_velocity.angle = if input & INPUT_LEFT != 0 {
-            0.1
+            steering.turn_rate
         } else if input & INPUT_RIGHT != 0 {
-            -0.1
+            -steering.turn_rate
         } else {
             0.
         };
 
-        if input & INPUT_UP != 0 {
+        thrust_engine.on =
+            input & INPUT_UP != 0 && energy.try_consume(THRUST_ENERGY_COST, ENERGY_REGEN_DELAY);
+        if thrust_engine.on {
             let dir = transform.rotation * Vec3::X;
-            velocity.x += dir.x * thrust_engine.force;
-            velocity.y += dir.y * thrust_engine.force;
+            let mut force = thrust_engine.force * powerups.thrust_factor();
+            if flags.iter().any(|flag| flag.carried_by(player.handle)) {
+                force *= CARRIER_THRUST_FACTOR;
+            }
+            velocity.x += dir.x * force;
+            velocity.y += dir.y * force;
         } else {
             if velocity.x.abs() > velocity.y.abs() {
                 if velocity.x < 0. {
@@ -71,15 +104,12 @@ pub fn apply_inputs(
     }
 }
 
-pub fn camera_system(
-    mut camera: Query<&mut Transform, (With<Camera>, Without<PlayerEntity>)>,
-    mut player: Query<(&mut Transform, &PlayerEntity), (With<PlayerEntity>, Without<Camera>)>,
-) {
-    for mut transform in camera.iter_mut() {
-        for (player_tf, whoami) in player.iter() {
-            if whoami.ego {
-                transform.translation = player_tf.translation;
-            }
+pub fn energy_regen_system(mut query: Query<&mut Energy>) {
+    for mut energy in query.iter_mut() {
+        if energy.regen_delay > 0 {
+            energy.regen_delay -= 1;
+        } else if energy.value < energy.max {
+            energy.value = (energy.value + ENERGY_REGEN).min(energy.max);
         }
     }
 }
@@ -109,7 +139,7 @@ pub fn movable_system(
                 || translation.x > ARENA_SIZE / 2. + MARGIN
                 || translation.x < -ARENA_SIZE / 2. - MARGIN
             {
-                commands.entity(entity).despawn();
+                commands.entity(entity).despawn_recursive();
             }
         } else {
             if translation.y >= ARENA_SIZE / 2. {
@@ -132,94 +162,324 @@ pub fn player_fire_system(
     mut commands: Commands,
     inputs: Res<Vec<(Input, InputStatus)>>,
     game_textures: Res<ImageAssets>,
-    mut query: Query<(&Transform, &PlayerEntity, &Velocity), With<Rollback>>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &PlayerEntity,
+            &Velocity,
+            &mut Weapon,
+            &mut Energy,
+            &PowerUps,
+        ),
+        With<Rollback>,
+    >,
+    mut scores: Query<&mut PlayerScore>,
     mut rip: ResMut<RollbackIdProvider>,
 ) {
-    for (player_tf, player, player_velocity) in query.iter_mut() {
+    // fire in handle order so projectiles get the same rollback ids on every peer
+    let mut shooters: Vec<(usize, Entity)> = query
+        .iter()
+        .map(|(entity, _, player, _, _, _, _)| (player.handle, entity))
+        .collect();
+    shooters.sort_unstable();
+
+    for (_, entity) in shooters {
+        let (_, player_tf, player, player_velocity, mut weapon, mut energy, powerups) =
+            query.get_mut(entity).unwrap();
         let input = match inputs[player.handle].1 {
             InputStatus::Confirmed => inputs[player.handle].0.inp,
             InputStatus::Predicted => inputs[player.handle].0.inp,
             InputStatus::Disconnected => 0, // disconnected players do nothing
         };
-        if input & INPUT_SPACE != 0 {
-            let laser_texture: Handle<Image>;
-            if player.team {
-                laser_texture = game_textures.ennemy_laser.clone();
-            } else {
-                laser_texture = game_textures.ally_laser.clone();
+        if input & INPUT_SWITCH != 0 {
+            weapon.switch();
+        }
+        let affordable = energy.value >= weapon.stats().energy_cost;
+        if weapon.update(input & INPUT_SPACE != 0, affordable) {
+            energy.try_consume(weapon.stats().energy_cost, ENERGY_REGEN_DELAY);
+            let fired = fire_weapon(
+                &mut commands,
+                &mut rip,
+                &game_textures,
+                &weapon,
+                player,
+                player_tf,
+                player_velocity,
+                powerups.damage_factor(),
+            );
+            for mut score in scores.iter_mut() {
+                if score.handle == player.handle {
+                    score.shots_fired += fired;
+                }
             }
-            let dir = player_tf.rotation * Vec3::X;
-            commands
-                .spawn_bundle(SpriteBundle {
-                    texture: laser_texture,
-                    transform: Transform {
-                        translation: Vec3::new(
-                            player_tf.translation.x,
-                            player_tf.translation.y,
-                            2.,
-                        ),
-                        rotation: player_tf
-                            .rotation
-                            .mul_quat(Quat::from_rotation_z((-90.0_f32).to_radians())),
-                        scale: Vec3::new(LASER_SCALE, LASER_SCALE, 1.),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .insert(Movable {
-                    auto_despawn: true,
-                    steerable: false,
-                })
-                .insert(Velocity {
-                    x: dir.x + player_velocity.x,
-                    y: dir.y + player_velocity.y,
-                })
-                .insert(AngularVelocity { angle: 0. })
-                .insert(Rollback::new(rip.next_id()))
-                .insert(Laser {
-                    player_handle: player.handle,
-                    player_team: player.team,
-                    size: match player.team {
-                        true => Vec2::new(17.0, 55.0),
-                        false => Vec2::new(9.0, 54.0),
-                    },
-                })
-                .insert(RoundEntity);
         }
     }
 }
 
+pub fn update_broadphase(
+    mut broadphase: ResMut<Broadphase>,
+    query: Query<(Entity, &Rollback, &Transform, &Hitbox)>,
+) {
+    // insert in rollback id order so every peer sees the same candidate order
+    let mut items: Vec<_> = query.iter().collect();
+    items.sort_by_key(|(_, rollback, _, _)| rollback.id());
+
+    broadphase.clear();
+    for (entity, rollback, transform, hitbox) in items {
+        broadphase.insert(
+            (rollback.id(), entity),
+            transform.translation.xy(),
+            hitbox.bounding_radius(transform),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn laser_hit_system(
     mut commands: Commands,
     game_textures: Res<ImageAssets>,
-    mut lasers: Query<(Entity, &Transform, &Laser), (With<Laser>, With<Rollback>)>,
-    mut players: Query<(Entity, &Transform, &PlayerEntity), (With<PlayerEntity>, With<Rollback>)>,
+    broadphase: Res<Broadphase>,
+    mode: Res<GameMode>,
+    options: Res<MatchOptions>,
+    mut lasers: Query<
+        (Entity, &Rollback, &Transform, &Laser, &Hitbox),
+        (With<Laser>, With<Rollback>),
+    >,
+    mut players: Query<
+        (
+            Entity,
+            &Transform,
+            &PlayerEntity,
+            &Hitbox,
+            &mut Health,
+            &mut PowerUps,
+        ),
+        (With<PlayerEntity>, With<Rollback>),
+    >,
+    mut scores: Query<&mut PlayerScore>,
 ) {
-    for (laser_entity, laser_tf, laser) in lasers.iter() {
-        for (player_entity, player_tf, player) in players.iter() {
-            if player.team != laser.player_team {
-                let laser_scale = Vec2::from(laser_tf.scale.xy());
-                let enemy_scale = Vec2::from(player_tf.scale.xy());
-
-                let collision = collide(
-                    laser_tf.translation,
-                    laser.size,
-                    player_tf.translation,
-                    player.size,
-                );
-
-                if let Some(_) = collision {
+    let rules = mode.rules(&options);
+    let mut sorted_lasers: Vec<_> = lasers.iter().collect();
+    sorted_lasers.sort_by_key(|(_, rollback, _, _, _)| rollback.id());
+
+    let mut candidates = Vec::new();
+    for (laser_entity, _, laser_tf, laser, laser_hitbox) in sorted_lasers {
+        broadphase.query(
+            laser_tf.translation.xy(),
+            laser_hitbox.bounding_radius(laser_tf),
+            &mut candidates,
+        );
+        for (_, candidate) in candidates.iter() {
+            let (player_entity, player_tf, player, player_hitbox, mut health, mut powerups) =
+                match players.get_mut(*candidate) {
+                    Ok(player) => player,
+                    Err(_) => continue,
+                };
+            // nobody shoots themselves, teammates only with friendly fire
+            if player.handle != laser.player_handle
+                && rules.can_hurt(laser.player_team, player.team)
+                && health.hp > 0.
+            {
+                if hitboxes_intersect(laser_hitbox, laser_tf, player_hitbox, player_tf) {
+                    let remaining = powerups.absorb(laser.damage);
+                    let hull_damage = remaining.min(health.hp);
+                    health.hp -= hull_damage;
+                    let damage = laser.damage - remaining + hull_damage;
+                    let killed = health.hp <= 0.;
+                    let enemies = are_enemies(laser.player_team, player.team);
+
+                    for mut score in scores.iter_mut() {
+                        if score.handle == laser.player_handle {
+                            score.shots_hit += 1;
+                            score.damage_dealt += damage;
+                            // team kills are not rewarded
+                            if killed && enemies {
+                                score.kills += 1;
+                            }
+                        } else if score.handle == player.handle && killed {
+                            score.deaths += 1;
+                            score.set_killed_by(laser.player_handle);
+                        }
+                    }
+
                     commands.spawn().insert(ExplosionToSpawn {
                         translation: laser_tf.translation.clone(),
                     });
-                    commands.entity(player_entity).despawn();
-                    commands.entity(laser_entity).despawn();
+                    if killed {
+                        commands.entity(player_entity).despawn_recursive();
+                    }
+                    commands.entity(laser_entity).despawn_recursive();
+                    break;
                 }
             }
         }
     }
 }
 
+struct ShipBody {
+    entity: Entity,
+    handle: usize,
+    team: Team,
+    radius: f32,
+    position: Vec2,
+    velocity: Vec2,
+    hp: f32,
+    shield: f32,
+}
+
+impl ShipBody {
+    /// Applies damage to the shield first, then the hull, and returns the damage dealt.
+    fn take_damage(&mut self, damage: f32) -> f32 {
+        let absorbed = damage.min(self.shield);
+        self.shield -= absorbed;
+        let hull = (damage - absorbed).min(self.hp);
+        self.hp -= hull;
+        absorbed + hull
+    }
+}
+
+pub fn ship_collision_system(
+    mut commands: Commands,
+    mut players: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &PlayerEntity,
+            &mut Health,
+            &mut PowerUps,
+            &Hitbox,
+        ),
+        With<Rollback>,
+    >,
+    mut scores: Query<&mut PlayerScore>,
+    broadphase: Res<Broadphase>,
+    mode: Res<GameMode>,
+    options: Res<MatchOptions>,
+) {
+    let rules = mode.rules(&options);
+    // resolve in handle order so every peer applies the same impulses
+    let mut ships: Vec<ShipBody> = players
+        .iter()
+        .filter(|(_, _, _, _, health, _, _)| health.hp > 0.)
+        .map(
+            |(entity, transform, velocity, player, health, powerups, hitbox)| ShipBody {
+                entity,
+                handle: player.handle,
+                team: player.team,
+                radius: hitbox.inner_radius(transform),
+                position: transform.translation.xy(),
+                velocity: Vec2::new(velocity.x, velocity.y),
+                hp: health.hp,
+                shield: powerups.shield,
+            },
+        )
+        .collect();
+    ships.sort_by_key(|ship| ship.handle);
+    let indices: HashMap<Entity, usize> = ships
+        .iter()
+        .enumerate()
+        .map(|(i, ship)| (ship.entity, i))
+        .collect();
+
+    let mut candidates = Vec::new();
+    let mut neighbours = Vec::new();
+    for i in 0..ships.len() {
+        broadphase.query(ships[i].position, ships[i].radius, &mut candidates);
+        neighbours.clear();
+        neighbours.extend(
+            candidates
+                .iter()
+                .filter_map(|(_, entity)| indices.get(entity).copied())
+                .filter(|j| *j > i),
+        );
+        neighbours.sort_unstable();
+
+        for &j in neighbours.iter() {
+            let (left, right) = ships.split_at_mut(j);
+            let a = &mut left[i];
+            let b = &mut right[0];
+            if a.hp <= 0. || b.hp <= 0. {
+                continue;
+            }
+
+            let delta = b.position - a.position;
+            let distance = delta.length();
+            let min_distance = a.radius + b.radius;
+            if distance >= min_distance {
+                continue;
+            }
+            let normal = if distance > f32::EPSILON {
+                delta / distance
+            } else {
+                Vec2::X
+            };
+
+            // push the ships apart so they no longer overlap
+            let push = normal * (min_distance - distance) / 2.;
+            a.position -= push;
+            b.position += push;
+
+            // elastic bounce between equal masses, along the contact normal
+            let closing_speed = -(b.velocity - a.velocity).dot(normal);
+            if closing_speed <= 0. {
+                continue;
+            }
+            let impulse = normal * closing_speed * (1. + SHIP_RESTITUTION) / 2.;
+            a.velocity -= impulse;
+            b.velocity += impulse;
+
+            if options.ram_damage && closing_speed > RAM_MIN_SPEED && rules.can_hurt(a.team, b.team)
+            {
+                let damage = (closing_speed - RAM_MIN_SPEED) * RAM_DAMAGE_FACTOR;
+                let damage_a = a.take_damage(damage);
+                let damage_b = b.take_damage(damage);
+
+                let enemies = are_enemies(a.team, b.team);
+                for mut score in scores.iter_mut() {
+                    let (damage_given, victim_hp, own_hp, other) = if score.handle == a.handle {
+                        (damage_b, b.hp, a.hp, b.handle)
+                    } else if score.handle == b.handle {
+                        (damage_a, a.hp, b.hp, a.handle)
+                    } else {
+                        continue;
+                    };
+                    if enemies {
+                        score.damage_dealt += damage_given;
+                        if victim_hp <= 0. {
+                            score.kills += 1;
+                        }
+                    }
+                    if own_hp <= 0. {
+                        score.deaths += 1;
+                        score.set_killed_by(other);
+                    }
+                }
+            }
+        }
+    }
+
+    for ship in ships.iter() {
+        if let Ok((entity, mut transform, mut velocity, _, mut health, mut powerups, _)) =
+            players.get_mut(ship.entity)
+        {
+            transform.translation.x = ship.position.x;
+            transform.translation.y = ship.position.y;
+            velocity.x = ship.velocity.x;
+            velocity.y = ship.velocity.y;
+            health.hp = ship.hp;
+            powerups.shield = ship.shield;
+            if ship.hp <= 0. {
+                commands.spawn().insert(ExplosionToSpawn {
+                    translation: transform.translation,
+                });
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
 pub fn explosion_animation_system(
     mut commands: Commands,
     time: Res<Time>,
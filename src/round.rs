@@ -0,0 +1,375 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+use ggrs::InputStatus;
+
+use crate::{
+    components::{Health, Input, PlayerEntity, PlayerScore, RoundEntity, SessionEntity},
+    game::{INPUT_READY, INPUT_SWAP},
+    modes::{
+        ctf::FlagScore, ffa::leader, koth::HillScore, survival::Waves, GameMode, MatchOptions,
+        Team, NO_TEAM, TEAM_A, TEAM_B,
+    },
+};
+
+/// Series length picked in the menus.
+pub struct SeriesSettings {
+    pub best_of: u32,
+}
+
+impl Default for SeriesSettings {
+    fn default() -> Self {
+        Self { best_of: 1 }
+    }
+}
+
+impl SeriesSettings {
+    pub fn cycle(&mut self) {
+        self.best_of = match self.best_of {
+            1 => 3,
+            3 => 5,
+            _ => 1,
+        };
+    }
+}
+
+/// What the local players asked for between rounds, sent along with the inputs.
+#[derive(Default)]
+pub struct NextRoundVote {
+    /// Ready for the next round, or for a rematch once the series is over.
+    pub ready: bool,
+    /// Rematch with the teams swapped, only if everyone asks for it.
+    pub swap_sides: bool,
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct RoundTimer {
+    /// Frames simulated since the round started.
+    pub frame: u32,
+    /// The time ran out on a tie, the next lead wins.
+    pub overtime: bool,
+    /// Frame at which the round was decided, 0 until then.
+    pub ended_at: u32,
+    /// Winning team, or winning handle without teams. [`NO_TEAM`] for a draw
+    /// or a defeat against the invaders.
+    pub winner: u8,
+}
+
+impl RoundTimer {
+    /// Frames left before the time limit, if any.
+    pub fn remaining(&self, time_limit: Option<u32>) -> Option<u32> {
+        time_limit.map(|limit| limit.saturating_sub(self.frame))
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Series {
+    pub best_of: u32,
+    /// Current round, starting at 1.
+    pub round: u32,
+    /// Rounds won by each team, or by each handle without teams.
+    pub wins: Vec<u32>,
+    /// Frames simulated since the session started.
+    pub frame: u32,
+    /// Frame at which the current round started.
+    pub started_at: u32,
+    /// Set on the frame the next round gets set up.
+    pub restarting: bool,
+    /// Teams are swapped, after a rematch on swapped sides.
+    pub swapped: bool,
+    /// Handles that left the match through the menu, one bit each.
+    pub left: u32,
+}
+
+impl Series {
+    /// Side holding the majority of the rounds, if any.
+    pub fn champion(&self) -> Option<u8> {
+        self.wins
+            .iter()
+            .position(|wins| *wins > self.best_of / 2)
+            .map(|side| side as u8)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.best_of <= 1 || self.champion().is_some()
+    }
+
+    pub fn has_left(&self, handle: usize) -> bool {
+        self.left & (1 << handle) != 0
+    }
+}
+
+/// Handles whose leaving flag got confirmed, for the session to disconnect
+/// them. Filled from the rollback schedule, but only with confirmed inputs.
+#[derive(Default)]
+pub struct LeftPlayers {
+    pub handles: Vec<usize>,
+}
+
+pub fn setup_series(
+    mut commands: Commands,
+    mut rip: ResMut<RollbackIdProvider>,
+    mode: Res<GameMode>,
+    settings: Res<SeriesSettings>,
+) {
+    // nobody wins a survival game, there is nothing to play a series for
+    let best_of = if *mode == GameMode::Survival {
+        1
+    } else {
+        settings.best_of
+    };
+
+    commands
+        .spawn()
+        .insert(Series {
+            best_of,
+            round: 1,
+            wins: vec![0; mode.num_players().max(2)],
+            ..Default::default()
+        })
+        .insert(RoundTimer::default())
+        .insert(Rollback::new(rip.next_id()))
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+}
+
+/// Ends the round on the mode's own win condition, on elimination or when the
+/// time runs out. A tie at the time limit goes to overtime, where the next
+/// lead wins.
+#[allow(clippy::too_many_arguments)]
+pub fn round_system(
+    mode: Res<GameMode>,
+    options: Res<MatchOptions>,
+    mut rounds: Query<(&mut RoundTimer, &mut Series)>,
+    waves: Query<&Waves>,
+    flag_scores: Query<&FlagScore>,
+    hill_scores: Query<&HillScore>,
+    scores: Query<&PlayerScore>,
+    players: Query<(&PlayerEntity, &Health)>,
+) {
+    let (mut timer, mut series) = match rounds.iter_mut().next() {
+        Some(round) => round,
+        None => return,
+    };
+    timer.frame += 1;
+    if timer.ended_at > 0 {
+        return;
+    }
+
+    let rules = mode.rules(&options);
+    let mut alive = [0u32; 2];
+    for (player, health) in players.iter() {
+        if health.hp > 0. && (player.team as usize) < alive.len() {
+            alive[player.team as usize] += 1;
+        }
+    }
+
+    // decisive outcomes first, they don't wait for the clock
+    let mut outcome = match *mode {
+        GameMode::Survival => waves
+            .iter()
+            .find(|waves| waves.overrun_at > 0)
+            .map(|_| NO_TEAM),
+        GameMode::CaptureTheFlag => flag_scores.iter().find_map(|score| score.winner()),
+        GameMode::KingOfTheHill => hill_scores.iter().find_map(|score| score.winner()),
+        GameMode::FreeForAll => rules
+            .frag_limit
+            .filter(|limit| scores.iter().any(|score| score.kills >= *limit))
+            .and_then(|_| leader(scores.iter()))
+            .map(|score| score.handle as u8),
+        GameMode::Versus => None,
+    };
+    if outcome.is_none() && *mode != GameMode::Survival && *mode != GameMode::FreeForAll {
+        outcome = match alive {
+            [0, 0] => Some(NO_TEAM),
+            [0, _] => Some(TEAM_B),
+            [_, 0] => Some(TEAM_A),
+            _ => None,
+        };
+    }
+
+    let out_of_time = rules.time_limit.map_or(false, |limit| timer.frame >= limit);
+    if outcome.is_none() && (out_of_time || timer.overtime) {
+        let standings: Vec<(Team, u32)> = match *mode {
+            GameMode::Versus => vec![(TEAM_A, alive[0]), (TEAM_B, alive[1])],
+            GameMode::CaptureTheFlag => flag_scores
+                .iter()
+                .flat_map(|score| [(TEAM_A, score.captures_a), (TEAM_B, score.captures_b)])
+                .collect(),
+            GameMode::KingOfTheHill => hill_scores
+                .iter()
+                .flat_map(|score| [(TEAM_A, score.points_a), (TEAM_B, score.points_b)])
+                .collect(),
+            GameMode::FreeForAll => scores
+                .iter()
+                .map(|score| (score.handle as u8, score.kills))
+                .collect(),
+            GameMode::Survival => Vec::new(),
+        };
+        outcome = sole_leader(&standings);
+        if outcome.is_none() {
+            timer.overtime = true;
+        }
+    }
+
+    if let Some(winner) = outcome {
+        timer.ended_at = timer.frame;
+        timer.winner = winner;
+        if let Some(wins) = series.wins.get_mut(winner as usize) {
+            *wins += 1;
+        }
+    }
+}
+
+/// Side with the strictly highest value, if there is one.
+fn sole_leader(standings: &[(u8, u32)]) -> Option<u8> {
+    let best = standings.iter().map(|(_, value)| *value).max()?;
+    let mut leaders = standings.iter().filter(|(_, value)| *value == best);
+    match (leaders.next(), leaders.next()) {
+        (Some((side, _)), None) => Some(*side),
+        _ => None,
+    }
+}
+
+/// Drops the ships of the players who left through the menu as soon as their
+/// leaving flag comes in, on every peer alike.
+pub fn leave_system(
+    mut commands: Commands,
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    mut left_players: ResMut<LeftPlayers>,
+    mut series: Query<&mut Series>,
+    players: Query<(Entity, &PlayerEntity)>,
+) {
+    for (handle, (input, status)) in inputs.iter().enumerate() {
+        if input.leaving == 0 {
+            continue;
+        }
+        for mut series in series.iter_mut() {
+            series.left |= 1 << handle;
+        }
+        for (entity, player) in players.iter() {
+            if player.handle == handle {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        // a predicted flag could still be rolled back
+        if *status == InputStatus::Confirmed && !left_players.handles.contains(&handle) {
+            left_players.handles.push(handle);
+        }
+    }
+}
+
+/// Starts the next round of the series once every player voted for it, or a
+/// whole new series when it is over. The round is cleared here and set up
+/// again by the systems of the restart stage.
+pub fn series_system(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    mut rounds: Query<(&mut Series, &mut RoundTimer)>,
+    round_entities: Query<Entity, (With<RoundEntity>, Without<SessionEntity>)>,
+) {
+    let (mut series, mut timer) = match rounds.iter_mut().next() {
+        Some(round) => round,
+        None => return,
+    };
+    series.frame += 1;
+    series.restarting = false;
+    if timer.ended_at == 0 {
+        return;
+    }
+
+    // players who left don't hold the others back
+    let voted = |flag: u8| {
+        inputs
+            .iter()
+            .all(|(input, status)| *status == InputStatus::Disconnected || input.inp & flag != 0)
+    };
+    if !voted(INPUT_READY) {
+        return;
+    }
+
+    for entity in round_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if series.is_over() {
+        // rematch
+        series.round = 1;
+        series.wins.iter_mut().for_each(|wins| *wins = 0);
+        if mode.has_teams() && voted(INPUT_SWAP) {
+            series.swapped = !series.swapped;
+        }
+    } else {
+        series.round += 1;
+    }
+    series.started_at = series.frame;
+    series.restarting = true;
+    *timer = RoundTimer::default();
+}
+
+/// The local game is paused from the pause menu. It goes through the inputs so
+/// that resimulated frames hold still the same way.
+fn is_paused(inputs: &[(Input, InputStatus)]) -> bool {
+    inputs.iter().any(|(input, _)| input.paused != 0)
+}
+
+/// Run criteria of the stages simulating the round.
+pub fn simulation_running(inputs: Res<Vec<(Input, InputStatus)>>) -> ShouldRun {
+    if is_paused(&inputs) {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+/// Run criteria of the stage setting up the next round.
+pub fn round_restarting(
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    rounds: Query<&Series>,
+) -> ShouldRun {
+    if !is_paused(&inputs) && rounds.iter().any(|series| series.restarting) {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(best_of: u32, wins: &[u32]) -> Series {
+        Series {
+            best_of,
+            round: 1,
+            wins: wins.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sole_leader_needs_a_strict_lead() {
+        assert_eq!(sole_leader(&[(TEAM_A, 3), (TEAM_B, 1)]), Some(TEAM_A));
+        assert_eq!(sole_leader(&[(0, 2), (1, 5), (2, 5)]), None);
+        assert_eq!(sole_leader(&[(0, 0), (1, 0)]), None);
+        assert_eq!(sole_leader(&[]), None);
+    }
+
+    #[test]
+    fn champion_holds_the_majority_of_rounds() {
+        assert_eq!(series(3, &[1, 1]).champion(), None);
+        assert_eq!(series(3, &[0, 2]).champion(), Some(TEAM_B));
+        assert_eq!(series(5, &[2, 2]).champion(), None);
+        assert_eq!(series(5, &[3, 1]).champion(), Some(TEAM_A));
+        // without teams every handle counts its own rounds
+        assert_eq!(series(3, &[1, 0, 0, 2]).champion(), Some(3));
+    }
+
+    #[test]
+    fn series_is_over_with_a_champion_or_a_single_round() {
+        assert!(!series(3, &[1, 1]).is_over());
+        assert!(series(3, &[2, 1]).is_over());
+        assert!(series(1, &[0, 0]).is_over());
+    }
+}
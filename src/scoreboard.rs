@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{PlayerScore, RoundEntity, SessionEntity},
+    menu::win::ScoreLine,
+    FontAssets, BUTTON_TEXT,
+};
+
+const SCOREBOARD_KEY: KeyCode = KeyCode::Tab;
+const SCOREBOARD_BACKGROUND: Color = Color::rgba(0.05, 0.05, 0.05, 0.8);
+
+#[derive(Component)]
+pub struct ScoreboardUI;
+
+#[derive(Component)]
+pub struct ScoreboardText;
+
+pub fn setup_scoreboard(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        padding: Rect::all(Val::Px(24.)),
+                        ..Default::default()
+                    },
+                    color: SCOREBOARD_BACKGROUND.into(),
+                    visibility: Visibility { is_visible: false },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                ScoreLine::HEADER,
+                                TextStyle {
+                                    font: font_assets.mono_font.clone(),
+                                    font_size: 24.,
+                                    color: BUTTON_TEXT,
+                                },
+                                Default::default(),
+                            ),
+                            visibility: Visibility { is_visible: false },
+                            ..Default::default()
+                        })
+                        .insert(ScoreboardText)
+                        .insert(ScoreboardUI);
+                })
+                .insert(ScoreboardUI);
+        })
+        .insert(ScoreboardUI)
+        .insert(RoundEntity)
+        .insert(SessionEntity);
+}
+
+pub fn toggle_scoreboard(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<&mut Visibility, With<ScoreboardUI>>,
+) {
+    let shown = keyboard_input.pressed(SCOREBOARD_KEY);
+    for mut visibility in query.iter_mut() {
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+    }
+}
+
+pub fn update_scoreboard(
+    keyboard_input: Res<Input<KeyCode>>,
+    scores: Query<&PlayerScore>,
+    mut text_query: Query<&mut Text, With<ScoreboardText>>,
+) {
+    if !keyboard_input.pressed(SCOREBOARD_KEY) {
+        return;
+    }
+
+    let table = ScoreLine::format_table(&ScoreLine::table(scores.iter()));
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = table.clone();
+    }
+}
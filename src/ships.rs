@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use ggrs::InputStatus;
+use serde::Deserialize;
+
+use crate::{
+    collision::Hitbox,
+    components::{Health, Input, PlayerEntity, ThrustEngine},
+    modes::TEAM_A,
+};
+
+/// Class used until a player's choice has been received.
+pub const DEFAULT_SHIP_CLASS: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AbilityKind {
+    Dash,
+    Cloak,
+    ShieldBurst,
+}
+
+impl AbilityKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AbilityKind::Dash => "Dash",
+            AbilityKind::Cloak => "Cloak",
+            AbilityKind::ShieldBurst => "Shield burst",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShipClass {
+    pub name: String,
+    pub thrust: f32,
+    pub turn_rate: f32,
+    pub hull: f32,
+    pub hitbox: (f32, f32),
+    pub sprite_scale: f32,
+    pub ally_sprite: String,
+    pub enemy_sprite: String,
+    pub ability: AbilityKind,
+}
+
+/// Ship classes read from `assets/data/ships.ron`.
+///
+/// The file is embedded at compile time so every peer, including the wasm
+/// build, runs the exact same stats.
+pub struct ShipClasses(pub Vec<ShipClass>);
+
+impl ShipClasses {
+    pub fn load() -> Self {
+        let classes: Vec<ShipClass> = ron::from_str(include_str!("../assets/data/ships.ron"))
+            .expect("Could not parse ship classes.");
+        assert!(!classes.is_empty(), "No ship class defined.");
+        Self(classes)
+    }
+
+    pub fn get(&self, id: u8) -> &ShipClass {
+        &self.0[id as usize % self.0.len()]
+    }
+}
+
+/// Ship class picked in the menus for the local player(s).
+pub struct SelectedShip(pub u8);
+
+impl Default for SelectedShip {
+    fn default() -> Self {
+        Self(DEFAULT_SHIP_CLASS)
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct ShipClassId {
+    pub id: u8,
+    /// Set once the class came from a confirmed input and cannot change anymore.
+    pub locked: bool,
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Steering {
+    pub turn_rate: f32,
+}
+
+/// Picks up each player's class from their inputs and applies its stats.
+///
+/// The class travels in [`Input::ship`], offset by one so that zeroed,
+/// predicted inputs leave the current class untouched. It locks on the first
+/// confirmed input, so every peer settles on the same class.
+pub fn ship_class_system(
+    classes: Res<ShipClasses>,
+    inputs: Res<Vec<(Input, InputStatus)>>,
+    mut query: Query<(
+        &mut PlayerEntity,
+        &mut ShipClassId,
+        &mut ThrustEngine,
+        &mut Steering,
+        &mut Health,
+        &mut Hitbox,
+        &mut Transform,
+    )>,
+) {
+    for (
+        mut player,
+        mut class_id,
+        mut thrust,
+        mut steering,
+        mut health,
+        mut hitbox,
+        mut transform,
+    ) in query.iter_mut()
+    {
+        let (input, status) = &inputs[player.handle];
+        if !class_id.locked && *status != InputStatus::Disconnected && input.ship > 0 {
+            let wanted = (input.ship - 1) % classes.0.len() as u8;
+            if wanted != class_id.id {
+                class_id.id = wanted;
+                health.hp = classes.get(wanted).hull;
+            }
+            class_id.locked = *status == InputStatus::Confirmed;
+        }
+
+        let class = classes.get(class_id.id);
+        let size = Vec2::new(class.hitbox.0, class.hitbox.1);
+        thrust.force = class.thrust;
+        steering.turn_rate = class.turn_rate;
+        health.max = class.hull;
+        health.hp = health.hp.min(class.hull);
+        player.size = size;
+        *hitbox = Hitbox::Obb {
+            half_extents: size / 2.,
+        };
+        transform.scale = Vec3::new(class.sprite_scale, class.sprite_scale, 1.);
+    }
+}
+
+pub fn update_ship_sprites(
+    asset_server: Res<AssetServer>,
+    classes: Res<ShipClasses>,
+    mut query: Query<(&PlayerEntity, &ShipClassId, &mut Handle<Image>), Changed<ShipClassId>>,
+) {
+    for (player, class_id, mut texture) in query.iter_mut() {
+        let class = classes.get(class_id.id);
+        let sprite = if player.team == TEAM_A {
+            &class.ally_sprite
+        } else {
+            &class.enemy_sprite
+        };
+        *texture = asset_server.load(sprite.as_str());
+    }
+}
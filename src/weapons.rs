@@ -0,0 +1,284 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_ggrs::{Rollback, RollbackIdProvider};
+
+use crate::{
+    collision::Hitbox,
+    components::{AngularVelocity, Laser, Movable, PlayerEntity, RoundEntity, Velocity},
+    modes::{are_enemies, player_color, survival::Invader, NO_TEAM, TEAM_A, TEAM_B},
+    ImageAssets, LASER_SCALE,
+};
+
+pub const RAPID_BLASTER: u8 = 0;
+pub const SPREAD_SHOT: u8 = 1;
+pub const CHARGED_BEAM: u8 = 2;
+pub const HOMING_MISSILE: u8 = 3;
+
+/// Frames during which a freshly selected weapon cannot fire.
+const SWITCH_DELAY: u32 = 15;
+
+pub struct WeaponStats {
+    pub name: &'static str,
+    /// Frames between two shots.
+    pub fire_interval: u32,
+    /// Projectile speed, relative to the ship's facing direction.
+    pub projectile_speed: f32,
+    pub damage: f32,
+    pub projectiles: u32,
+    /// Angle between two projectiles of the same volley, in radians.
+    pub spread: f32,
+    /// Frames the trigger must be held before the shot is released, 0 fires on press.
+    pub charge_frames: u32,
+    /// Maximum steering per frame towards the closest enemy, in radians.
+    pub homing: f32,
+    pub scale: f32,
+    /// Energy spent by each volley.
+    pub energy_cost: f32,
+}
+
+pub static WEAPONS: [WeaponStats; 4] = [
+    WeaponStats {
+        name: "Rapid blaster",
+        fire_interval: 8,
+        projectile_speed: 1.2,
+        damage: 20.,
+        projectiles: 1,
+        spread: 0.,
+        charge_frames: 0,
+        homing: 0.,
+        scale: LASER_SCALE,
+        energy_cost: 6.,
+    },
+    WeaponStats {
+        name: "Spread shot",
+        fire_interval: 30,
+        projectile_speed: 1.,
+        damage: 25.,
+        projectiles: 3,
+        spread: 0.2,
+        charge_frames: 0,
+        homing: 0.,
+        scale: LASER_SCALE,
+        energy_cost: 15.,
+    },
+    WeaponStats {
+        name: "Charged beam",
+        fire_interval: 30,
+        projectile_speed: 2.,
+        damage: 100.,
+        projectiles: 1,
+        spread: 0.,
+        charge_frames: 45,
+        homing: 0.,
+        scale: 1.,
+        energy_cost: 35.,
+    },
+    WeaponStats {
+        name: "Homing missile",
+        fire_interval: 60,
+        projectile_speed: 0.6,
+        damage: 50.,
+        projectiles: 1,
+        spread: 0.,
+        charge_frames: 0,
+        homing: 0.05,
+        scale: 0.8,
+        energy_cost: 25.,
+    },
+];
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Weapon {
+    pub kind: u8,
+    /// Frames left before the weapon can fire again.
+    pub cooldown: u32,
+    /// Frames the trigger has been held for charged weapons.
+    pub charge: u32,
+}
+
+impl Weapon {
+    pub fn stats(&self) -> &'static WeaponStats {
+        &WEAPONS[self.kind as usize % WEAPONS.len()]
+    }
+
+    pub fn switch(&mut self) {
+        self.kind = (self.kind + 1) % WEAPONS.len() as u8;
+        self.charge = 0;
+        self.cooldown = self.cooldown.max(SWITCH_DELAY);
+    }
+
+    /// Advances the weapon by one frame and returns whether it fires.
+    /// A shot that is not `affordable` does not fire and does not start the cooldown.
+    pub fn update(&mut self, trigger: bool, affordable: bool) -> bool {
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+        }
+        let stats = self.stats();
+
+        let fire = if stats.charge_frames > 0 {
+            if trigger {
+                self.charge = (self.charge + 1).min(stats.charge_frames);
+                false
+            } else {
+                let charged = self.charge >= stats.charge_frames;
+                self.charge = 0;
+                charged
+            }
+        } else {
+            trigger
+        };
+
+        if fire && affordable && self.cooldown == 0 {
+            self.cooldown = stats.fire_interval;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Default, Reflect, Component)]
+#[reflect(Component)]
+pub struct Homing {
+    pub turn_rate: f32,
+}
+
+/// Spawns the volley of the given weapon from a ship and returns the number of projectiles.
+pub fn fire_weapon(
+    commands: &mut Commands,
+    rip: &mut RollbackIdProvider,
+    game_textures: &ImageAssets,
+    weapon: &Weapon,
+    player: &PlayerEntity,
+    player_tf: &Transform,
+    player_velocity: &Velocity,
+    damage_factor: f32,
+) -> u32 {
+    let stats = weapon.stats();
+    let laser_size = match player.team {
+        TEAM_A => Vec2::new(9.0, 54.0),
+        _ => Vec2::new(17.0, 55.0),
+    };
+
+    for i in 0..stats.projectiles {
+        let offset = (i as f32 - (stats.projectiles - 1) as f32 / 2.) * stats.spread;
+        let rotation = player_tf.rotation * Quat::from_rotation_z(offset);
+        let dir = rotation * Vec3::X * stats.projectile_speed;
+
+        let laser = Laser {
+            player_handle: player.handle,
+            player_team: player.team,
+            size: laser_size,
+            damage: stats.damage * damage_factor,
+        };
+        let transform = Transform {
+            translation: Vec3::new(player_tf.translation.x, player_tf.translation.y, 2.),
+            rotation: rotation.mul_quat(Quat::from_rotation_z((-90.0_f32).to_radians())),
+            scale: Vec3::new(stats.scale, stats.scale, 1.),
+            ..Default::default()
+        };
+        let mut projectile = commands.spawn_bundle(laser_bundle(game_textures, &laser, transform));
+        projectile
+            .insert(Movable {
+                auto_despawn: true,
+                steerable: false,
+            })
+            .insert(Velocity {
+                x: dir.x + player_velocity.x,
+                y: dir.y + player_velocity.y,
+            })
+            .insert(AngularVelocity { angle: 0. })
+            .insert(Rollback::new(rip.next_id()))
+            .insert(laser)
+            .insert(Hitbox::Obb {
+                half_extents: laser_size / 2.,
+            })
+            .insert(RoundEntity);
+        if stats.homing > 0. {
+            projectile.insert(Homing {
+                turn_rate: stats.homing,
+            });
+        }
+    }
+
+    stats.projectiles
+}
+
+/// Sprite of a laser, tinted with the shooter's colour when there are no teams.
+pub fn laser_bundle(
+    game_textures: &ImageAssets,
+    laser: &Laser,
+    transform: Transform,
+) -> SpriteBundle {
+    SpriteBundle {
+        texture: if laser.player_team == TEAM_A {
+            game_textures.ally_laser.clone()
+        } else {
+            game_textures.ennemy_laser.clone()
+        },
+        transform,
+        sprite: Sprite {
+            color: if laser.player_team == NO_TEAM {
+                player_color(laser.player_handle)
+            } else {
+                Color::WHITE
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn homing_system(
+    mut missiles: Query<
+        (&mut Transform, &mut Velocity, &Laser, &Homing),
+        (Without<PlayerEntity>, Without<Invader>),
+    >,
+    players: Query<(&Transform, &PlayerEntity), Without<Homing>>,
+    invaders: Query<(&Transform, &Rollback), (With<Invader>, Without<Homing>)>,
+) {
+    for (mut transform, mut velocity, laser, homing) in missiles.iter_mut() {
+        let position = transform.translation.xy();
+
+        // closest enemy, invaders being on the second team, ties broken by
+        // handle or rollback id so every peer picks the same target
+        let enemy_ships = players
+            .iter()
+            .filter(|(_, player)| {
+                player.handle != laser.player_handle && are_enemies(laser.player_team, player.team)
+            })
+            .map(|(player_tf, player)| (player_tf, (0, player.handle)));
+        let invaders = invaders
+            .iter()
+            .filter(|_| are_enemies(laser.player_team, TEAM_B))
+            .map(|(invader_tf, rollback)| (invader_tf, (1, rollback.id() as usize)));
+        let target = enemy_ships
+            .chain(invaders)
+            .map(|(target_tf, id)| {
+                (
+                    target_tf.translation.xy().distance_squared(position),
+                    id,
+                    target_tf.translation.xy(),
+                )
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        if let Some((_, _, target)) = target {
+            let current = Vec2::new(velocity.x, velocity.y);
+            let speed = current.length();
+            let current_angle = current.y.atan2(current.x);
+            let wanted = target - position;
+            let mut delta = wanted.y.atan2(wanted.x) - current_angle;
+            if delta > std::f32::consts::PI {
+                delta -= 2. * std::f32::consts::PI;
+            } else if delta < -std::f32::consts::PI {
+                delta += 2. * std::f32::consts::PI;
+            }
+            let angle = current_angle + delta.clamp(-homing.turn_rate, homing.turn_rate);
+
+            velocity.x = angle.cos() * speed;
+            velocity.y = angle.sin() * speed;
+            transform.rotation = Quat::from_rotation_z(angle - 90.0_f32.to_radians());
+        }
+    }
+}